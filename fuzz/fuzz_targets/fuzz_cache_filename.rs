@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use webfont_zh::utils::{generate_cache_filename, parse_cache_filename};
+
+fuzz_target!(|data: &[u8]| {
+    let codepoints: Vec<u32> = data
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+    
+    let filename = generate_cache_filename(&codepoints);
+    // 文件名会拼接到缓存目录下，不能跳出该目录
+    assert!(!filename.contains(".."));
+    assert!(!filename.starts_with('/'));
+    
+    if !codepoints.is_empty() {
+        let mut sorted = codepoints.clone();
+        sorted.sort_unstable();
+        assert_eq!(parse_cache_filename(&filename), Some(sorted));
+    }
+});
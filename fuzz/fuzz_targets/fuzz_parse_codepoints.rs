@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use webfont_zh::utils::{parse_codepoints, parse_codepoints_range};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = parse_codepoints(input);
+    if let Ok(codepoints) = parse_codepoints_range(input) {
+        assert!(codepoints.iter().all(|&cp| cp <= 0x10FFFF));
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::Once;
+use webfont_zh::font::FontProcessor;
+
+/// 每次最多子集化的码点数量，避免大字体拖慢单次执行
+const MAX_CODEPOINTS: usize = 64;
+
+static RESTORE_PANIC_HOOK: Once = Once::new();
+
+fuzz_target!(|data: &[u8]| {
+    // libfuzzer-sys的panic钩子会直接abort，而 `create_subset` 会捕获HarfBuzz的panic并返回错误，
+    // 恢复默认钩子后只有逃逸出目标函数的panic才算崩溃
+    RESTORE_PANIC_HOOK.call_once(|| drop(std::panic::take_hook()));
+    
+    let Ok(processor) = FontProcessor::from_bytes(data.to_vec()) else {
+        return;
+    };
+    
+    let codepoints: Vec<u32> = processor
+        .get_all_codepoints()
+        .into_iter()
+        .take(MAX_CODEPOINTS)
+        .collect();
+    let _ = processor.generate_woff2(&codepoints);
+});
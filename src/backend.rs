@@ -0,0 +1,302 @@
+use crate::{
+    config::{AppConfig, FontConfig},
+    error::AppError,
+    font::{FontMetrics, FontProcessor, HintingType, KernPair, LigatureEntry, NameEntry, Os2Metadata, TableEntry, VariableAxis, Woff2Header},
+    service::{CacheCompactReport, CacheManifestEntry, CachedFont, CmapPage, CoverageRow, EmojiCoverage, FontBatchSummary, FontFileInfo, FontInfo, FontService, FontVerifyReport, GlyphPage},
+    subsetter::BackgroundSubsetter,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::HashMap, path::Path};
+
+/// 处理器使用的字体服务接口，测试中可以替换为 `test_utils::MockFontService`
+#[async_trait]
+pub trait FontBackend: Send + Sync {
+    fn config(&self) -> &AppConfig;
+    
+    fn subsetter(&self) -> &BackgroundSubsetter;
+    
+    /// 记录一次字体请求中的码点
+    fn record_request(&self, codepoints: &[u32]);
+    
+    /// 记录一次返回了指定字体子集的请求
+    fn record_font_served(&self, font_id: &str);
+    
+    fn most_used_fonts(&self, top_n: usize) -> Vec<(String, u64)>;
+    
+    /// 检查提交的字体配置，返回所有错误信息
+    fn validate_font_config(&self, font_config: &FontConfig) -> Vec<String>;
+    
+    async fn list_fonts(&self) -> Vec<FontInfo>;
+    
+    async fn fonts_covering_codepoint(&self, codepoint: u32) -> Vec<FontInfo>;
+    
+    async fn least_covered_for(&self, codepoints: &[u32]) -> Vec<(String, f64)>;
+    
+    async fn font_source_url(&self, font_id: &str) -> Result<Option<String>, AppError>;
+    
+    async fn font_annotations(&self, font_id: &str) -> Result<HashMap<String, String>, AppError>;
+    
+    async fn update_annotations(
+        &self,
+        font_id: &str,
+        patch: HashMap<String, Option<String>>,
+    ) -> Result<HashMap<String, String>, AppError>;
+    
+    async fn rename_font(&self, old_id: &str, new_id: &str) -> Result<(), AppError>;
+    
+    async fn name_table(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<NameEntry>>>, AppError>;
+    
+    async fn os2_metadata(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<Os2Metadata>>>, AppError>;
+    
+    async fn cmap(&self, font_id: &str, page: usize, per_page: usize) -> Result<Vec<FontFileInfo<CmapPage>>, AppError>;
+    
+    async fn hinting_types(&self, font_id: &str) -> Result<Vec<FontFileInfo<HintingType>>, AppError>;
+    
+    async fn cff_private_dicts(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<HashMap<String, serde_json::Value>>>>, AppError>;
+    
+    async fn variable_axes(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<VariableAxis>>>, AppError>;
+    
+    async fn kern_tables(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<KernPair>>>, AppError>;
+    
+    async fn table_entries(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<TableEntry>>>, AppError>;
+    
+    async fn ligatures(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<LigatureEntry>>>, AppError>;
+    
+    async fn metrics(&self, font_id: &str) -> Result<Vec<FontFileInfo<FontMetrics>>, AppError>;
+    
+    async fn coverage_heatmap(&self, font_id: &str) -> Result<Vec<CoverageRow>, AppError>;
+    
+    async fn font_ranges(&self, font_id: &str) -> Result<Vec<String>, AppError>;
+    
+    async fn emoji_coverage(&self, font_id: &str) -> Result<EmojiCoverage, AppError>;
+    
+    async fn list_glyphs(&self, font_id: &str, page: usize, per_page: usize) -> Result<GlyphPage, AppError>;
+    
+    async fn fallback_sources(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<(String, Vec<u32>)>, AppError>;
+    
+    async fn detect_tofu(&self, font_id: &str, codepoints: &[u32]) -> Vec<u32>;
+    
+    /// 把分配给多个字体的码点合并为一个字体
+    async fn get_merged_font(&self, sources: &[(String, Vec<u32>)], ttf: bool, preserve_hints: bool) -> Result<CachedFont, AppError>;
+    
+    async fn get_cached_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError>;
+    
+    async fn get_cached_ttf(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError>;
+    
+    async fn get_cached_unhinted_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError>;
+    
+    async fn get_subset_by_hash(&self, font_id: &str, hash: &str) -> Result<CachedFont, AppError>;
+    
+    async fn woff2_header(&self, font_id: &str, codepoints: &[u32]) -> Result<Woff2Header, AppError>;
+    
+    async fn estimate_subset_size(&self, font_id: &str, codepoints: &[u32]) -> Result<usize, AppError>;
+    
+    async fn glyph_png(&self, font_id: &str, codepoint: u32, size: u32) -> Result<Vec<u8>, AppError>;
+    
+    async fn regenerate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<(), AppError>;
+    
+    async fn rebuild_cache(&self, font_id: &str) -> Result<usize, AppError>;
+    
+    async fn install_font_archive(&self, data: Vec<u8>) -> Result<FontBatchSummary, AppError>;
+    
+    fn cache_entries(&self) -> Vec<CacheManifestEntry>;
+    
+    async fn count_subsets(&self, font_id: &str) -> Result<usize, AppError>;
+    
+    async fn export_cache_manifest(&self, output_path: &Path) -> Result<()>;
+    
+    async fn verify_all_fonts(&self) -> Result<FontVerifyReport, AppError>;
+    
+    async fn compact_cache(&self) -> Result<CacheCompactReport, AppError>;
+}
+
+#[async_trait]
+impl FontBackend for FontService {
+    fn config(&self) -> &AppConfig {
+        FontService::config(self)
+    }
+    
+    fn subsetter(&self) -> &BackgroundSubsetter {
+        FontService::subsetter(self)
+    }
+    
+    fn record_request(&self, codepoints: &[u32]) {
+        FontService::record_request(self, codepoints)
+    }
+    
+    fn record_font_served(&self, font_id: &str) {
+        FontService::record_font_served(self, font_id)
+    }
+    
+    fn most_used_fonts(&self, top_n: usize) -> Vec<(String, u64)> {
+        FontService::most_used_fonts(self, top_n)
+    }
+    
+    fn validate_font_config(&self, font_config: &FontConfig) -> Vec<String> {
+        FontService::validate_font_config(self, font_config)
+    }
+    
+    async fn list_fonts(&self) -> Vec<FontInfo> {
+        FontService::list_fonts(self).await
+    }
+    
+    async fn fonts_covering_codepoint(&self, codepoint: u32) -> Vec<FontInfo> {
+        FontService::fonts_covering_codepoint(self, codepoint).await
+    }
+    
+    async fn least_covered_for(&self, codepoints: &[u32]) -> Vec<(String, f64)> {
+        FontService::least_covered_for(self, codepoints).await
+    }
+    
+    async fn font_source_url(&self, font_id: &str) -> Result<Option<String>, AppError> {
+        FontService::font_source_url(self, font_id).await
+    }
+    
+    async fn font_annotations(&self, font_id: &str) -> Result<HashMap<String, String>, AppError> {
+        FontService::font_annotations(self, font_id).await
+    }
+    
+    async fn update_annotations(
+        &self,
+        font_id: &str,
+        patch: HashMap<String, Option<String>>,
+    ) -> Result<HashMap<String, String>, AppError> {
+        FontService::update_annotations(self, font_id, patch).await
+    }
+    
+    async fn rename_font(&self, old_id: &str, new_id: &str) -> Result<(), AppError> {
+        FontService::rename_font(self, old_id, new_id).await
+    }
+    
+    async fn name_table(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<NameEntry>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::name_table_entries).await
+    }
+    
+    async fn os2_metadata(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<Os2Metadata>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::os2_metadata).await
+    }
+    
+    async fn cmap(&self, font_id: &str, page: usize, per_page: usize) -> Result<Vec<FontFileInfo<CmapPage>>, AppError> {
+        self.inspect_font_files(font_id, |processor| {
+            CmapPage::paginate(processor.cmap_entries(), page, per_page)
+        })
+        .await
+    }
+    
+    async fn hinting_types(&self, font_id: &str) -> Result<Vec<FontFileInfo<HintingType>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::hinting_type).await
+    }
+    
+    async fn cff_private_dicts(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<HashMap<String, serde_json::Value>>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::cff_private_dict).await
+    }
+    
+    async fn variable_axes(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<VariableAxis>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::variable_axes).await
+    }
+    
+    async fn kern_tables(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<KernPair>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::full_kern_table).await
+    }
+    
+    async fn table_entries(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<TableEntry>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::table_entries).await
+    }
+    
+    async fn ligatures(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<LigatureEntry>>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::ligatures).await
+    }
+    
+    async fn metrics(&self, font_id: &str) -> Result<Vec<FontFileInfo<FontMetrics>>, AppError> {
+        self.inspect_font_files(font_id, FontProcessor::metrics).await
+    }
+    
+    async fn coverage_heatmap(&self, font_id: &str) -> Result<Vec<CoverageRow>, AppError> {
+        FontService::coverage_heatmap(self, font_id).await
+    }
+    
+    async fn font_ranges(&self, font_id: &str) -> Result<Vec<String>, AppError> {
+        FontService::font_ranges(self, font_id).await
+    }
+    
+    async fn emoji_coverage(&self, font_id: &str) -> Result<EmojiCoverage, AppError> {
+        FontService::emoji_coverage(self, font_id).await
+    }
+    
+    async fn list_glyphs(&self, font_id: &str, page: usize, per_page: usize) -> Result<GlyphPage, AppError> {
+        FontService::list_glyphs(self, font_id, page, per_page).await
+    }
+    
+    async fn fallback_sources(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<(String, Vec<u32>)>, AppError> {
+        FontService::fallback_sources(self, font_id, codepoints).await
+    }
+    
+    async fn detect_tofu(&self, font_id: &str, codepoints: &[u32]) -> Vec<u32> {
+        FontService::detect_tofu(self, font_id, codepoints).await
+    }
+    
+    async fn get_merged_font(&self, sources: &[(String, Vec<u32>)], ttf: bool, preserve_hints: bool) -> Result<CachedFont, AppError> {
+        FontService::get_merged_font(self, sources, ttf, preserve_hints).await
+    }
+    
+    async fn get_cached_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        FontService::get_cached_font(self, font_id, codepoints).await
+    }
+    
+    async fn get_cached_ttf(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        FontService::get_cached_ttf(self, font_id, codepoints).await
+    }
+    
+    async fn get_cached_unhinted_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        FontService::get_cached_unhinted_font(self, font_id, codepoints).await
+    }
+    
+    async fn get_subset_by_hash(&self, font_id: &str, hash: &str) -> Result<CachedFont, AppError> {
+        FontService::get_subset_by_hash(self, font_id, hash).await
+    }
+    
+    async fn woff2_header(&self, font_id: &str, codepoints: &[u32]) -> Result<Woff2Header, AppError> {
+        FontService::woff2_header(self, font_id, codepoints).await
+    }
+    
+    async fn estimate_subset_size(&self, font_id: &str, codepoints: &[u32]) -> Result<usize, AppError> {
+        FontService::estimate_subset_size(self, font_id, codepoints).await
+    }
+    
+    async fn glyph_png(&self, font_id: &str, codepoint: u32, size: u32) -> Result<Vec<u8>, AppError> {
+        FontService::glyph_png(self, font_id, codepoint, size).await
+    }
+    
+    async fn regenerate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<(), AppError> {
+        FontService::regenerate_font(self, font_id, codepoints).await
+    }
+    
+    async fn rebuild_cache(&self, font_id: &str) -> Result<usize, AppError> {
+        FontService::rebuild_cache(self, font_id).await
+    }
+    
+    async fn install_font_archive(&self, data: Vec<u8>) -> Result<FontBatchSummary, AppError> {
+        FontService::install_font_archive(self, data).await
+    }
+    
+    fn cache_entries(&self) -> Vec<CacheManifestEntry> {
+        FontService::cache_entries(self)
+    }
+    
+    async fn count_subsets(&self, font_id: &str) -> Result<usize, AppError> {
+        FontService::count_subsets(self, font_id).await
+    }
+    
+    async fn export_cache_manifest(&self, output_path: &Path) -> Result<()> {
+        FontService::export_cache_manifest(self, output_path).await
+    }
+    
+    async fn verify_all_fonts(&self) -> Result<FontVerifyReport, AppError> {
+        FontService::verify_all_fonts(self).await
+    }
+    
+    async fn compact_cache(&self) -> Result<CacheCompactReport, AppError> {
+        FontService::compact_cache(self).await
+    }
+}
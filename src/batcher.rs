@@ -0,0 +1,151 @@
+use crate::service::FontService;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tokio::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// 一批最多合并的写入数量
+const MAX_BATCH_SIZE: usize = 50;
+
+/// 第一个写入进入缓冲区后最多等待的时间
+const MAX_BATCH_DELAY: Duration = Duration::from_millis(100);
+
+/// 缓冲区最多积压的写入数量，超出后由调用方直接写入
+const QUEUE_CAPACITY: usize = 256;
+
+/// 等待写入磁盘的缓存文件
+struct PendingWrite {
+    font_id: String,
+    codepoints: Vec<u32>,
+    data: Arc<Vec<u8>>,
+    queued_at: SystemTime,
+}
+
+/// 合并缓存文件的写入，攒够50个或等待100毫秒后依次落盘，减少短时间内大量小文件写入的开销
+///
+/// 写入完成前的文件内容可以通过 `pending` 读取
+pub struct WriteBatcher {
+    sender: mpsc::Sender<PathBuf>,
+    pending: Arc<Mutex<HashMap<PathBuf, PendingWrite>>>,
+}
+
+impl Default for WriteBatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteBatcher {
+    /// 创建缓冲区并启动后台写入任务，需要在Tokio运行时中调用
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        tokio::spawn(Self::run(receiver, pending.clone()));
+        
+        Self { sender, pending }
+    }
+    
+    /// 把缓存文件放入写入缓冲区，缓冲区已满时返回 `false`
+    pub fn submit(&self, path: &Path, font_id: &str, codepoints: &[u32], data: Vec<u8>) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.contains_key(path) {
+            return true;
+        }
+        let Ok(permit) = self.sender.try_reserve() else {
+            return false;
+        };
+        
+        pending.insert(
+            path.to_path_buf(),
+            PendingWrite {
+                font_id: font_id.to_string(),
+                codepoints: codepoints.to_vec(),
+                data: Arc::new(data),
+                queued_at: SystemTime::now(),
+            },
+        );
+        permit.send(path.to_path_buf());
+        true
+    }
+    
+    /// 尚未写入磁盘的缓存文件内容，以及放入缓冲区的时间
+    pub fn pending(&self, path: &Path) -> Option<(Vec<u8>, SystemTime)> {
+        let pending = self.pending.lock().unwrap();
+        let write = pending.get(path)?;
+        Some((write.data.to_vec(), write.queued_at))
+    }
+    
+    async fn run(mut receiver: mpsc::Receiver<PathBuf>, pending: Arc<Mutex<HashMap<PathBuf, PendingWrite>>>) {
+        let mut batch = Vec::with_capacity(MAX_BATCH_SIZE);
+        while let Some(path) = receiver.recv().await {
+            batch.push(path);
+            let deadline = Instant::now() + MAX_BATCH_DELAY;
+            while batch.len() < MAX_BATCH_SIZE {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(path)) => batch.push(path),
+                    _ => break,
+                }
+            }
+            
+            log::debug!("批量写入 {} 个缓存文件", batch.len());
+            for path in batch.drain(..) {
+                let Some((font_id, codepoints, data)) = pending
+                    .lock()
+                    .unwrap()
+                    .get(&path)
+                    .map(|write| (write.font_id.clone(), write.codepoints.clone(), write.data.clone()))
+                else {
+                    continue;
+                };
+                // 写入完成后才移出缓冲区，读取方总能在缓冲区或磁盘上找到文件
+                write_cache_file(&path, &font_id, &codepoints, &data).await;
+                pending.lock().unwrap().remove(&path);
+            }
+        }
+    }
+}
+
+/// 保存缓存文件并写入扩展属性，失败时只记录警告
+pub async fn write_cache_file(path: &Path, font_id: &str, codepoints: &[u32], data: &[u8]) -> bool {
+    match store_cache_file(path, font_id, codepoints, data).await {
+        Ok(()) => {
+            log::info!("保存缓存文件: {:?}", path);
+            true
+        }
+        Err(e) => {
+            log::warn!("保存缓存文件失败 {:?}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// 先写入同目录下的临时文件再重命名覆盖目标文件
+///
+/// 去重后的缓存文件可能与其他文件共用一个inode，原地覆盖会同时改写所有硬链接，
+/// 重命名只替换目标路径，其他链接保持原有内容和扩展属性
+pub async fn store_cache_file(path: &Path, font_id: &str, codepoints: &[u32], data: &[u8]) -> std::io::Result<()> {
+    let Some(parent) = path.parent() else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "缓存文件路径没有上级目录"));
+    };
+    tokio::fs::create_dir_all(parent).await?;
+    
+    // 临时文件不以 `.woff2` 结尾，不会被当作缓存子集列出
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    let result = async {
+        tokio::fs::write(&temp_path, data).await?;
+        FontService::write_cache_xattr(&temp_path, font_id, codepoints);
+        tokio::fs::rename(&temp_path, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result
+}
@@ -0,0 +1,207 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Top DICT中 `Private` 操作符，操作数为私有字典的大小和偏移
+const OP_PRIVATE: u16 = 18;
+/// Top DICT中 `FDArray` 操作符（12 36），CID字体的每个字体字典都有自己的私有字典
+const OP_FD_ARRAY: u16 = 0x0C00 | 36;
+/// 私有字典中 `Subrs` 操作符，值为子程序的偏移，没有展示意义
+const OP_SUBRS: u16 = 19;
+
+/// 读取CFF表的Private DICT，CID字体返回FDArray中第一个字体字典的私有字典
+///
+/// 数组类型的值（如 `BlueValues`）已由差值还原为绝对值
+pub fn private_dict(cff: &[u8]) -> Option<HashMap<String, Value>> {
+    let header_size = usize::from(*cff.get(2)?);
+    let name_index_end = index_end(cff, header_size)?;
+    let top_dict = index_items(cff, name_index_end)?.into_iter().next()?;
+    let top_entries = parse_dict(top_dict)?;
+    
+    let private_operands = match top_entries.iter().find(|(op, _)| *op == OP_FD_ARRAY) {
+        Some((_, operands)) => {
+            let fd_array = index_items(cff, operand_offset(operands.first()?)?)?;
+            let font_dict = parse_dict(fd_array.into_iter().next()?)?;
+            font_dict.into_iter().find(|(op, _)| *op == OP_PRIVATE)?.1
+        }
+        None => top_entries.into_iter().find(|(op, _)| *op == OP_PRIVATE)?.1,
+    };
+    let [size, offset] = private_operands[..] else {
+        return None;
+    };
+    let (size, offset) = (operand_offset(&size)?, operand_offset(&offset)?);
+    let private = cff.get(offset..offset.checked_add(size)?)?;
+    
+    let dict = parse_dict(private)?
+        .into_iter()
+        .filter(|(op, _)| *op != OP_SUBRS)
+        .map(|(op, operands)| (operator_name(op), operator_value(op, operands)))
+        .collect();
+    Some(dict)
+}
+
+/// 跳过一个INDEX结构，返回其后的偏移
+fn index_end(data: &[u8], start: usize) -> Option<usize> {
+    let (offsets, data_start) = index_offsets(data, start)?;
+    data_start.checked_add(*offsets.last()?)
+}
+
+/// 读取INDEX结构中的所有条目
+fn index_items(data: &[u8], start: usize) -> Option<Vec<&[u8]>> {
+    let (offsets, data_start) = index_offsets(data, start)?;
+    offsets
+        .windows(2)
+        .map(|pair| data.get(data_start + pair[0]..data_start + pair[1]))
+        .collect()
+}
+
+/// INDEX中从0开始的条目偏移和数据区起点
+fn index_offsets(data: &[u8], start: usize) -> Option<(Vec<usize>, usize)> {
+    let count = usize::from(u16::from_be_bytes([*data.get(start)?, *data.get(start + 1)?]));
+    if count == 0 {
+        return Some((vec![0], start + 2));
+    }
+    
+    let offset_size = usize::from(*data.get(start + 2)?);
+    if !(1..=4).contains(&offset_size) {
+        return None;
+    }
+    let offsets_start = start + 3;
+    let offsets = (0..=count)
+        .map(|i| {
+            let bytes = data.get(offsets_start + i * offset_size..offsets_start + (i + 1) * offset_size)?;
+            // 偏移从1开始
+            let offset = bytes.iter().fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+            offset.checked_sub(1)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+        return None;
+    }
+    Some((offsets, offsets_start + (count + 1) * offset_size))
+}
+
+/// 解析DICT数据，返回按出现顺序排列的操作符和操作数，双字节操作符编码为 `0x0C00 | 第二字节`
+fn parse_dict(data: &[u8]) -> Option<Vec<(u16, Vec<f64>)>> {
+    let mut entries = Vec::new();
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let b0 = data[i];
+        match b0 {
+            12 => {
+                entries.push((0x0C00 | u16::from(*data.get(i + 1)?), std::mem::take(&mut operands)));
+                i += 2;
+            }
+            0..=21 => {
+                entries.push((u16::from(b0), std::mem::take(&mut operands)));
+                i += 1;
+            }
+            28 => {
+                operands.push(f64::from(i16::from_be_bytes([*data.get(i + 1)?, *data.get(i + 2)?])));
+                i += 3;
+            }
+            29 => {
+                let bytes = data.get(i + 1..i + 5)?;
+                operands.push(f64::from(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])));
+                i += 5;
+            }
+            30 => {
+                let (value, length) = parse_real(&data[i + 1..])?;
+                operands.push(value);
+                i += 1 + length;
+            }
+            32..=246 => {
+                operands.push(f64::from(i32::from(b0) - 139));
+                i += 1;
+            }
+            247..=250 => {
+                operands.push(f64::from((i32::from(b0) - 247) * 256 + i32::from(*data.get(i + 1)?) + 108));
+                i += 2;
+            }
+            251..=254 => {
+                operands.push(f64::from(-(i32::from(b0) - 251) * 256 - i32::from(*data.get(i + 1)?) - 108));
+                i += 2;
+            }
+            _ => return None,
+        }
+    }
+    Some(entries)
+}
+
+/// 解析以半字节编码的实数，返回值和占用的字节数
+fn parse_real(data: &[u8]) -> Option<(f64, usize)> {
+    let mut text = String::new();
+    for (index, &byte) in data.iter().enumerate() {
+        for nibble in [byte >> 4, byte & 0x0F] {
+            match nibble {
+                0..=9 => text.push(char::from(b'0' + nibble)),
+                0xA => text.push('.'),
+                0xB => text.push('E'),
+                0xC => text.push_str("E-"),
+                0xE => text.push('-'),
+                0xF => return Some((text.parse().ok()?, index + 1)),
+                _ => return None,
+            }
+        }
+    }
+    None
+}
+
+/// 作为偏移或大小使用的操作数
+fn operand_offset(value: &f64) -> Option<usize> {
+    (value.fract() == 0.0 && *value >= 0.0).then_some(*value as usize)
+}
+
+fn operator_name(op: u16) -> String {
+    let name = match op {
+        6 => "BlueValues",
+        7 => "OtherBlues",
+        8 => "FamilyBlues",
+        9 => "FamilyOtherBlues",
+        10 => "StdHW",
+        11 => "StdVW",
+        20 => "defaultWidthX",
+        21 => "nominalWidthX",
+        0x0C09 => "BlueScale",
+        0x0C0A => "BlueShift",
+        0x0C0B => "BlueFuzz",
+        0x0C0C => "StemSnapH",
+        0x0C0D => "StemSnapV",
+        0x0C0E => "ForceBold",
+        0x0C11 => "LanguageGroup",
+        0x0C12 => "ExpansionFactor",
+        0x0C13 => "initialRandomSeed",
+        0x0C00..=0x0CFF => return format!("12 {}", op & 0xFF),
+        _ => return op.to_string(),
+    };
+    name.to_string()
+}
+
+/// 差值数组还原为绝对值，单个操作数直接返回数值
+fn operator_value(op: u16, operands: Vec<f64>) -> Value {
+    let is_delta = matches!(op, 6..=9 | 0x0C0C | 0x0C0D);
+    if is_delta {
+        let mut current = 0.0;
+        let values = operands
+            .into_iter()
+            .map(|delta| {
+                current += delta;
+                number(current)
+            })
+            .collect();
+        return Value::Array(values);
+    }
+    
+    match operands[..] {
+        [value] => number(value),
+        _ => Value::Array(operands.into_iter().map(number).collect()),
+    }
+}
+
+fn number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Value::from(value)
+    }
+}
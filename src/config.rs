@@ -1,12 +1,62 @@
-use anyhow::Result;
+use crate::{
+    charsets::GB2312_FREQUENT_3500,
+    utils::{is_font_file, parse_unicode_ranges, unicode_block_range},
+};
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppConfig {
     pub data_dir: PathBuf,
     pub static_dir: PathBuf,
     pub cache_cleanup_days: u64,
+    /// 服务器监听地址
+    pub bind_address: IpAddr,
+    /// 请求体大小上限（字节）
+    pub max_body_bytes: usize,
+    /// 字体压缩包上传大小上限（字节）
+    pub max_upload_bytes: usize,
+    /// 字体压缩包解压后的总大小上限（字节）
+    pub max_archive_extract_bytes: u64,
+    /// 单次请求最少的码点数量
+    pub min_codepoints_per_request: usize,
+    /// 字体回退链的最大深度
+    pub max_fallback_depth: usize,
+    /// 启动时的缓存预热策略
+    pub warmup: WarmupStrategy,
+    /// 管理接口的访问令牌，未设置时管理接口不可用
+    #[serde(skip_serializing)]
+    pub admin_token: Option<String>,
+    /// HTTPS证书配置，未设置时使用HTTP
+    pub tls: Option<TlsConfig>,
+    /// 访问 `/static/` 时生成缓存文件索引页
+    pub serve_static_index: bool,
+    /// HarfBuzz子集化失败后的重试次数，最多 `MAX_SUBSET_RETRIES` 次
+    pub subset_retry_count: u8,
+    /// Tokio工作线程数，未设置时使用全部CPU核心
+    pub worker_threads: Option<usize>,
+    /// HTTP/1连接等待下一个请求头的最长时间（秒），超时后关闭空闲或发送过慢的连接
+    pub keep_alive_timeout_secs: u64,
+    /// 请求审计日志文件，每个请求追加一行JSON，按UTC日期写入 `{文件名}.YYYY-MM-DD`，未设置时不记录
+    pub request_log_path: Option<PathBuf>,
+}
+
+/// 子集化重试次数上限
+pub const MAX_SUBSET_RETRIES: u8 = 3;
+
+/// 单节点部署时直接提供HTTPS所需的PEM证书和私钥，生产环境建议使用反向代理
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TlsConfig {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
 }
 
 impl Default for AppConfig {
@@ -15,13 +65,120 @@ impl Default for AppConfig {
             data_dir: PathBuf::from("data"),
             static_dir: PathBuf::from("data/static"),
             cache_cleanup_days: 7,
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            max_body_bytes: 1024 * 1024,
+            max_upload_bytes: 512 * 1024 * 1024,
+            max_archive_extract_bytes: 2 * 1024 * 1024 * 1024,
+            min_codepoints_per_request: 1,
+            max_fallback_depth: 5,
+            warmup: WarmupStrategy::None,
+            admin_token: None,
+            tls: None,
+            serve_static_index: true,
+            subset_retry_count: 0,
+            worker_threads: None,
+            keep_alive_timeout_secs: 75,
+            request_log_path: None,
         }
     }
 }
 
 impl AppConfig {
     pub fn load() -> Result<Self> {
-        let config = Self::default();
+        let defaults = Self::default();
+        let bind_address = match std::env::var("WEBFONT_BIND") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                log::warn!("无效的监听地址 {}: {}，使用默认值", value, e);
+                defaults.bind_address
+            }),
+            Err(_) => defaults.bind_address,
+        };
+        
+        let warmup = match std::env::var("WEBFONT_WARMUP") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                log::warn!("无效的预热策略 {}: {}，不进行预热", value, e);
+                WarmupStrategy::None
+            }),
+            Err(_) => WarmupStrategy::None,
+        };
+        
+        let tls = match (std::env::var("WEBFONT_TLS_CERT"), std::env::var("WEBFONT_TLS_KEY")) {
+            (Ok(cert_file), Ok(key_file)) => Some(TlsConfig {
+                cert_file: PathBuf::from(cert_file),
+                key_file: PathBuf::from(key_file),
+            }),
+            (Err(_), Err(_)) => None,
+            _ => return Err(anyhow!("WEBFONT_TLS_CERT 和 WEBFONT_TLS_KEY 必须同时设置")),
+        };
+        
+        let serve_static_index = match std::env::var("WEBFONT_STATIC_INDEX") {
+            Ok(value) => !matches!(value.trim(), "0" | "false" | "off"),
+            Err(_) => defaults.serve_static_index,
+        };
+        
+        let subset_retry_count = match std::env::var("WEBFONT_SUBSET_RETRIES") {
+            Ok(value) => match value.trim().parse::<u8>() {
+                Ok(count) if count > MAX_SUBSET_RETRIES => {
+                    log::warn!("子集化重试次数 {} 超过上限，使用 {}", count, MAX_SUBSET_RETRIES);
+                    MAX_SUBSET_RETRIES
+                }
+                Ok(count) => count,
+                Err(e) => {
+                    log::warn!("无效的子集化重试次数 {}: {}，不重试", value, e);
+                    defaults.subset_retry_count
+                }
+            },
+            Err(_) => defaults.subset_retry_count,
+        };
+        
+        let max_fallback_depth = match std::env::var("WEBFONT_MAX_FALLBACK_DEPTH") {
+            Ok(value) => value.trim().parse::<usize>().unwrap_or_else(|e| {
+                log::warn!("无效的最大回退深度 {}: {}，使用默认值 {}", value, e, defaults.max_fallback_depth);
+                defaults.max_fallback_depth
+            }),
+            Err(_) => defaults.max_fallback_depth,
+        };
+        
+        let worker_threads = match std::env::var("WEBFONT_WORKER_THREADS") {
+            Ok(value) => match value.trim().parse::<usize>() {
+                Ok(count) if count > 0 => Some(count),
+                _ => {
+                    log::warn!("无效的工作线程数 {}，使用全部CPU核心", value);
+                    None
+                }
+            },
+            Err(_) => defaults.worker_threads,
+        };
+        
+        let keep_alive_timeout_secs = match std::env::var("WEBFONT_KEEP_ALIVE_TIMEOUT") {
+            Ok(value) => match value.trim().parse::<u64>() {
+                Ok(secs) if secs > 0 => secs,
+                _ => {
+                    log::warn!("无效的keep-alive超时时间 {}，使用默认值 {}", value, defaults.keep_alive_timeout_secs);
+                    defaults.keep_alive_timeout_secs
+                }
+            },
+            Err(_) => defaults.keep_alive_timeout_secs,
+        };
+        
+        let config = Self {
+            bind_address,
+            warmup,
+            tls,
+            serve_static_index,
+            subset_retry_count,
+            max_fallback_depth,
+            worker_threads,
+            keep_alive_timeout_secs,
+            admin_token: std::env::var("WEBFONT_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            request_log_path: std::env::var("WEBFONT_REQUEST_LOG")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            ..defaults
+        };
         
         // 确保目录存在
         std::fs::create_dir_all(&config.data_dir)?;
@@ -30,23 +187,251 @@ impl AppConfig {
         
         Ok(config)
     }
+    
+    /// 检查数据目录、缓存目录和字体目录的访问权限，以及字体ID是否重复
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        for dir in [&self.data_dir, &self.static_dir] {
+            if !dir.is_dir() {
+                warnings.push(ConfigWarning::MissingDir(dir.clone()));
+            } else if !is_writable(dir) {
+                warnings.push(ConfigWarning::NotWritable(dir.clone()));
+            }
+        }
+        
+        let fonts_dir = self.data_dir.join("fonts");
+        let entries = match std::fs::read_dir(&fonts_dir) {
+            Ok(entries) => entries,
+            Err(_) if !fonts_dir.is_dir() => {
+                warnings.push(ConfigWarning::MissingDir(fonts_dir));
+                return warnings;
+            }
+            Err(_) => {
+                warnings.push(ConfigWarning::NotReadable(fonts_dir));
+                return warnings;
+            }
+        };
+        
+        // 配置无法解析的目录由加载字体时报告
+        let mut font_dirs: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for entry in entries.filter_map(|e| e.ok()).filter(|e| e.path().is_dir()) {
+            if let Ok(config) = FontConfig::load_from_dir(&entry.path()) {
+                font_dirs.entry(config.id).or_default().push(entry.path());
+            }
+        }
+        let mut duplicates: Vec<_> = font_dirs.into_iter().filter(|(_, dirs)| dirs.len() > 1).collect();
+        duplicates.sort();
+        for (id, mut dirs) in duplicates {
+            dirs.sort();
+            warnings.push(ConfigWarning::DuplicateFontId { id, dirs });
+        }
+        warnings
+    }
+}
+
+/// 启动前发现的配置问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    MissingDir(PathBuf),
+    NotReadable(PathBuf),
+    NotWritable(PathBuf),
+    /// 多个字体目录使用同一个ID，只有其中一个会被加载
+    DuplicateFontId { id: String, dirs: Vec<PathBuf> },
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::MissingDir(dir) => write!(f, "目录不存在: {}", dir.display()),
+            ConfigWarning::NotReadable(dir) => write!(f, "目录不可读: {}", dir.display()),
+            ConfigWarning::NotWritable(dir) => write!(f, "目录不可写: {}", dir.display()),
+            ConfigWarning::DuplicateFontId { id, dirs } => {
+                let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+                write!(f, "字体ID {} 重复: {}", id, dirs.join(", "))
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 在目录中创建并删除一个临时文件，判断目录是否可写
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".write-check-{}", std::process::id()));
+    let writable = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    writable
+}
+
+/// 缓存预热策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupStrategy {
+    /// 不预热
+    None,
+    /// 从文件读取字符列表，每行一个字符、十进制码点或 `U+XXXX`
+    FrequencyFile(PathBuf),
+    /// 内置字符集
+    Preset(CharsetPreset),
+    /// 历史请求中最常用的N个码点
+    TopN(usize),
+}
+
+impl FromStr for WarmupStrategy {
+    type Err = anyhow::Error;
+    
+    /// 解析 `none`、`file:<路径>`、`preset:<名称>`、`top:<数量>` 形式的配置
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.is_empty() || value == "none" {
+            return Ok(WarmupStrategy::None);
+        }
+        
+        match value.split_once(':') {
+            Some(("file", path)) => Ok(WarmupStrategy::FrequencyFile(PathBuf::from(path))),
+            Some(("preset", name)) => Ok(WarmupStrategy::Preset(name.parse()?)),
+            Some(("top", count)) => Ok(WarmupStrategy::TopN(count.parse()?)),
+            _ => Err(anyhow!("未知的预热策略: {}", value)),
+        }
+    }
+}
+
+/// 内置字符集
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CharsetPreset {
+    /// 可打印ASCII字符
+    Ascii,
+    /// CJK符号和标点以及全角字符
+    CjkPunctuation,
+    /// GB2312一级汉字中字频最高的3500个
+    #[serde(rename = "gb2312-frequent-3500")]
+    Gb2312Frequent3500,
+}
+
+impl CharsetPreset {
+    /// 字符集包含的码点
+    pub fn codepoints(&self) -> Vec<u32> {
+        match self {
+            CharsetPreset::Ascii => (0x20..=0x7E).collect(),
+            CharsetPreset::CjkPunctuation => (0x3000..=0x303F).chain(0xFF00..=0xFFEF).collect(),
+            CharsetPreset::Gb2312Frequent3500 => GB2312_FREQUENT_3500.to_vec(),
+        }
+    }
+}
+
+impl FromStr for CharsetPreset {
+    type Err = anyhow::Error;
+    
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "ascii" => Ok(CharsetPreset::Ascii),
+            "cjk-punctuation" => Ok(CharsetPreset::CjkPunctuation),
+            "gb2312-frequent-3500" => Ok(CharsetPreset::Gb2312Frequent3500),
+            _ => Err(anyhow!("未知的字符集: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FontConfig {
     pub id: String,
+    /// 继承的字体ID，未填写的 `version`、`license`、`fallback` 使用其值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default)]
     pub version: String,
     pub font_family: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<LocalizedText>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub title: Option<LocalizedText>,
-    pub fallback: Vec<String>,
+    /// 字体的简短介绍，如“泛中日韩字体”
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<LocalizedText>,
+    #[serde(default)]
+    pub fallback: Vec<FallbackEntry>,
+    #[serde(default)]
     pub license: String,
+    /// 字体至少包含的字形数量，不足时不加载
+    #[serde(default)]
+    pub min_codepoints_in_font: usize,
+    /// 字体上游项目地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
     pub files: Vec<FontFile>,
+    /// `overrides/` 目录中按Unicode区块命名的替换字体，如 `CJK-Unified` 对应 `overrides/CJK-Unified.ttf`，靠前的优先
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<String>,
+    /// 运维标签，如 `{"environment": "production"}`，可通过接口修改
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    /// 配置文件所在的字体目录
+    #[serde(skip)]
+    pub dir: PathBuf,
+    /// 所有字体文件都声明了 `sha256` 且校验通过
+    #[serde(skip)]
+    pub checksum_verified: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 回退字体，可以是本地字体ID或托管在CDN上的字体
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FallbackEntry {
+    Local(String),
+    External(ExternalFallback),
+}
+
+/// 外部回退字体，本地字体都不包含请求的字符时把地址返回给客户端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "ExternalFallbackConfig")]
+pub struct ExternalFallback {
+    /// 字体地址，`{char}` 替换为范围内的请求码点（逗号分隔）
+    pub url_pattern: String,
+    /// CSS `unicode-range` 格式的覆盖范围，如 `U+30000-3134F`
+    pub unicode_range: String,
+    /// 加载配置时从 `unicode_range` 解析出的码点区间
+    #[serde(skip)]
+    ranges: Vec<RangeInclusive<u32>>,
+}
+
+/// 配置文件中的外部回退字体，反序列化后解析覆盖范围
+#[derive(Deserialize)]
+struct ExternalFallbackConfig {
+    url_pattern: String,
+    unicode_range: String,
+}
+
+impl TryFrom<ExternalFallbackConfig> for ExternalFallback {
+    type Error = anyhow::Error;
+    
+    fn try_from(config: ExternalFallbackConfig) -> Result<Self> {
+        let ranges = parse_unicode_ranges(&config.unicode_range)
+            .map_err(|e| anyhow!("外部回退范围无效 {}: {}", config.unicode_range, e))?;
+        Ok(Self {
+            url_pattern: config.url_pattern,
+            unicode_range: config.unicode_range,
+            ranges,
+        })
+    }
+}
+
+impl ExternalFallback {
+    /// 返回落在覆盖范围内的请求码点
+    pub fn matching_codepoints(&self, codepoints: &[u32]) -> Vec<u32> {
+        codepoints
+            .iter()
+            .copied()
+            .filter(|cp| self.ranges.iter().any(|range| range.contains(cp)))
+            .collect()
+    }
+    
+    /// 用请求码点填充地址模板
+    pub fn url_for(&self, codepoints: &[u32]) -> String {
+        let chars = codepoints.iter().map(|cp| cp.to_string()).collect::<Vec<_>>().join(",");
+        self.url_pattern.replace("{char}", &chars)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LocalizedText {
     #[serde(rename = "zh-hans")]
     pub zh_hans: String,
@@ -54,25 +439,436 @@ pub struct LocalizedText {
     pub zh_hant: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl LocalizedText {
+    /// 按语言标签选择文本，繁体中文标签返回 `zh-hant`，其余返回 `zh-hans`
+    pub fn for_language(&self, language: &str) -> &str {
+        let language = language.to_lowercase();
+        let is_traditional = ["zh-hant", "zh-tw", "zh-hk", "zh-mo"]
+            .iter()
+            .any(|tag| language == *tag || language.starts_with(&format!("{}-", tag)));
+        
+        if is_traditional {
+            &self.zh_hant
+        } else {
+            &self.zh_hans
+        }
+    }
+    
+    /// 合并两份文本，两边都有的语言使用 `other` 的文本，`other` 为空的语言保留原文本
+    pub fn merge(&self, other: &LocalizedText) -> LocalizedText {
+        let pick = |ours: &String, theirs: &String| {
+            if theirs.is_empty() { ours.clone() } else { theirs.clone() }
+        };
+        LocalizedText {
+            zh_hans: pick(&self.zh_hans, &other.zh_hans),
+            zh_hant: pick(&self.zh_hant, &other.zh_hant),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FontFile {
     pub name: String,
     pub path: String,
     pub font_family: String,
+    /// 字体文件的SHA-256校验值，启动时校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl FontFile {
+    /// 路径是否为字体目录内的相对路径，不能是绝对路径或包含 `..`
+    pub fn is_within_font_dir(&self) -> bool {
+        Path::new(&self.path)
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+    }
 }
 
 impl FontConfig {
-    pub fn load_from_dir(font_dir: &PathBuf) -> Result<Self> {
+    pub fn load_from_dir(font_dir: &Path) -> Result<Self> {
         let config_path = font_dir.join("config.json");
         let content = std::fs::read_to_string(config_path)?;
-        let config: FontConfig = serde_json::from_str(&content)?;
+        let mut config: FontConfig = serde_json::from_str(&content)?;
+        config.dir = font_dir.to_path_buf();
         Ok(config)
     }
     
-    pub fn save_to_dir(&self, font_dir: &PathBuf) -> Result<()> {
+    /// 读取字体文件的name表生成最小配置，字体ID为文件所在的目录名
+    ///
+    /// 字体族名取name表的英文Family，版本去掉 `Version ` 前缀，文件名称使用PostScript名称
+    pub fn detect_from_font_file(path: &Path) -> Result<FontConfig> {
+        let data = std::fs::read(path)?;
+        let face = ttf_parser::Face::parse(&data, 0)
+            .map_err(|e| anyhow!("解析字体失败 {}: {:?}", path.display(), e))?;
+        // 优先使用英文名称，没有时使用第一条可解码的记录
+        let name = |name_id: u16| {
+            let names: Vec<_> = face.names().into_iter().filter(|name| name.name_id == name_id).collect();
+            names
+                .iter()
+                .filter(|name| name.language() == ttf_parser::Language::English_UnitedStates)
+                .chain(names.iter())
+                .find_map(|name| name.to_string())
+        };
+        
+        let font_family = name(ttf_parser::name_id::FAMILY)
+            .ok_or_else(|| anyhow!("字体文件缺少字体族名: {}", path.display()))?;
+        // 版本字符串通常形如 `Version 2.001;hotconv 1.0.118`
+        let version = name(ttf_parser::name_id::VERSION)
+            .map(|version| {
+                let version = version.trim();
+                let version = version.strip_prefix("Version ").unwrap_or(version);
+                version.split(';').next().unwrap_or_default().trim().to_string()
+            })
+            .unwrap_or_default();
+        let postscript_name = name(ttf_parser::name_id::POST_SCRIPT_NAME).unwrap_or_else(|| font_family.clone());
+        
+        let font_dir = path.parent().ok_or_else(|| anyhow!("无法确定字体目录: {}", path.display()))?;
+        let id = font_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("无法确定字体ID: {}", path.display()))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("无效的字体文件路径: {}", path.display()))?;
+        
+        Ok(FontConfig {
+            id,
+            extends: None,
+            version,
+            font_family: font_family.clone(),
+            name: None,
+            title: None,
+            description: None,
+            fallback: Vec::new(),
+            license: String::new(),
+            min_codepoints_in_font: 0,
+            source_url: None,
+            files: vec![FontFile {
+                name: postscript_name,
+                path: file_name,
+                font_family,
+                sha256: None,
+            }],
+            overrides: Vec::new(),
+            annotations: HashMap::new(),
+            dir: font_dir.to_path_buf(),
+            checksum_verified: false,
+        })
+    }
+    
+    /// 目录中没有 `config.json` 时，按文件名顺序读取所有字体文件生成配置并写入 `config.json`
+    ///
+    /// 第一个字体文件决定字体ID以外的字段，写入失败时仍使用生成的配置
+    pub fn detect_from_dir(font_dir: &Path) -> Result<FontConfig> {
+        let mut font_paths: Vec<PathBuf> = std::fs::read_dir(font_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && is_font_file(&path.file_name().unwrap_or_default().to_string_lossy()))
+            .collect();
+        font_paths.sort();
+        
+        let mut font_paths = font_paths.into_iter();
+        let first = font_paths
+            .next()
+            .ok_or_else(|| anyhow!("字体目录中没有config.json和字体文件: {}", font_dir.display()))?;
+        let mut config = Self::detect_from_font_file(&first)?;
+        for path in font_paths {
+            match Self::detect_from_font_file(&path) {
+                Ok(detected) => config.files.extend(detected.files),
+                Err(e) => log::warn!("跳过无法识别的字体文件 {}: {}", path.display(), e),
+            }
+        }
+        
         let config_path = font_dir.join("config.json");
-        let content = serde_json::to_string_pretty(self)?;
+        match config.save_to_dir(font_dir) {
+            Ok(()) => log::info!("未找到config.json，已根据字体文件自动生成: {}", config_path.display()),
+            Err(e) => log::warn!("写入自动生成的配置失败 {}: {}", config_path.display(), e),
+        }
+        Ok(config)
+    }
+    
+    /// 检查配置本身的问题，不访问文件系统，返回所有错误信息
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !is_valid_font_id(&self.id) {
+            errors.push(format!("无效的字体ID: {}", self.id));
+        }
+        if self.files.is_empty() {
+            errors.push("未配置字体文件".to_string());
+        }
+        for font_file in &self.files {
+            if !font_file.is_within_font_dir() {
+                errors.push(format!("字体文件路径必须位于字体目录内: {}", font_file.path));
+            }
+        }
+        for block in &self.overrides {
+            if unicode_block_range(block).is_none() {
+                errors.push(format!("未知的Unicode区块: {}", block));
+            }
+        }
+        errors
+    }
+    
+    /// 以与内置配置相同的四空格缩进写回 `config.json`
+    pub fn save_to_dir(&self, font_dir: &Path) -> Result<()> {
+        let config_path = font_dir.join("config.json");
+        let mut content = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        self.serialize(&mut serde_json::Serializer::with_formatter(&mut content, formatter))?;
+        content.push(b'\n');
         std::fs::write(config_path, content)?;
         Ok(())
     }
-}
\ No newline at end of file
+    
+    /// 用父配置的字段填充未指定的字段
+    fn inherit_from(&mut self, parent: &FontConfig) {
+        if self.version.is_empty() {
+            self.version = parent.version.clone();
+        }
+        if self.license.is_empty() {
+            self.license = parent.license.clone();
+        }
+        if self.fallback.is_empty() {
+            self.fallback = parent.fallback.clone();
+        }
+    }
+}
+
+/// 字体ID只能包含ASCII字母、数字、`-`、`_` 和 `.`，且不能以 `.` 开头
+pub fn is_valid_font_id(id: &str) -> bool {
+    !id.is_empty() && !id.starts_with('.') && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// 解析所有字体配置的 `extends` 继承链
+pub fn resolve_extends(configs: &mut HashMap<String, FontConfig>) -> Result<()> {
+    let ids: Vec<String> = configs.keys().cloned().collect();
+    
+    for id in ids {
+        // 收集继承链：[自身, 父, 祖父, ...]
+        let mut chain = vec![id.clone()];
+        let mut visited = HashSet::from([id.clone()]);
+        let mut current = id.clone();
+        while let Some(parent_id) = configs.get(&current).and_then(|c| c.extends.clone()) {
+            if !configs.contains_key(&parent_id) {
+                return Err(anyhow!("字体 {} 继承的字体不存在: {}", current, parent_id));
+            }
+            if !visited.insert(parent_id.clone()) {
+                chain.push(parent_id);
+                return Err(anyhow!("字体配置存在循环继承: {}", chain.join(" -> ")));
+            }
+            chain.push(parent_id.clone());
+            current = parent_id;
+        }
+        
+        // 从最顶层开始向下合并
+        for i in (0..chain.len() - 1).rev() {
+            let parent = configs[&chain[i + 1]].clone();
+            if let Some(child) = configs.get_mut(&chain[i]) {
+                child.inherit_from(&parent);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font_config(id: &str, extends: Option<&str>, license: &str) -> FontConfig {
+        FontConfig {
+            id: id.to_string(),
+            extends: extends.map(|s| s.to_string()),
+            version: String::new(),
+            font_family: id.to_string(),
+            name: None,
+            title: None,
+            description: None,
+            fallback: Vec::new(),
+            license: license.to_string(),
+            min_codepoints_in_font: 0,
+            source_url: None,
+            overrides: Vec::new(),
+            annotations: HashMap::new(),
+            files: Vec::new(),
+            dir: PathBuf::new(),
+            checksum_verified: false,
+        }
+    }
+
+    #[test]
+    fn test_localized_text_for_language() {
+        let text = LocalizedText {
+            zh_hans: "思源黑体".to_string(),
+            zh_hant: "思源黑體".to_string(),
+        };
+        assert_eq!(text.for_language("zh-TW"), "思源黑體");
+        assert_eq!(text.for_language("zh-hant-hk"), "思源黑體");
+        assert_eq!(text.for_language("zh-CN"), "思源黑体");
+        assert_eq!(text.for_language("zh"), "思源黑体");
+    }
+
+    #[test]
+    fn test_localized_text_merge() {
+        let runtime = LocalizedText {
+            zh_hans: "旧名称".to_string(),
+            zh_hant: "思源黑體".to_string(),
+        };
+        let on_disk = LocalizedText {
+            zh_hans: "思源黑体".to_string(),
+            zh_hant: String::new(),
+        };
+        let merged = runtime.merge(&on_disk);
+        assert_eq!(merged.zh_hans, "思源黑体");
+        assert_eq!(merged.zh_hant, "思源黑體");
+    }
+
+    #[test]
+    fn test_gb2312_frequent_3500_is_sorted_and_unique() {
+        let codepoints = CharsetPreset::Gb2312Frequent3500.codepoints();
+        assert_eq!(codepoints.len(), 3500);
+        assert!(codepoints.windows(2).all(|pair| pair[0] < pair[1]));
+        // 字频最高的“的”“一”“是”和常用的“中”“国”“汉”“字”必须包含在内
+        for c in ['的', '一', '是', '中', '国', '汉', '字'] {
+            assert!(codepoints.contains(&(c as u32)), "{}", c);
+        }
+        // GB2312二级汉字“亍”和一级汉字中字频较低的“贰”不在其中
+        assert!(!codepoints.contains(&('亍' as u32)));
+        assert!(!codepoints.contains(&('贰' as u32)));
+    }
+
+    #[test]
+    fn test_parse_warmup_strategy() {
+        assert_eq!("none".parse::<WarmupStrategy>().unwrap(), WarmupStrategy::None);
+        assert_eq!(
+            "file:data/chars.txt".parse::<WarmupStrategy>().unwrap(),
+            WarmupStrategy::FrequencyFile(PathBuf::from("data/chars.txt"))
+        );
+        assert_eq!(
+            "preset:ascii".parse::<WarmupStrategy>().unwrap(),
+            WarmupStrategy::Preset(CharsetPreset::Ascii)
+        );
+        assert_eq!("top:100".parse::<WarmupStrategy>().unwrap(), WarmupStrategy::TopN(100));
+        assert_eq!(
+            "preset:gb2312-frequent-3500".parse::<WarmupStrategy>().unwrap(),
+            WarmupStrategy::Preset(CharsetPreset::Gb2312Frequent3500)
+        );
+        assert!("preset:unknown".parse::<WarmupStrategy>().is_err());
+        assert!("top:many".parse::<WarmupStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_extends() {
+        let mut configs = HashMap::new();
+        let mut base = font_config("base", None, "OFL");
+        base.version = "1.0".to_string();
+        base.fallback = vec![FallbackEntry::Local("other".to_string())];
+        configs.insert("base".to_string(), base);
+        configs.insert("middle".to_string(), font_config("middle", Some("base"), ""));
+        configs.insert("child".to_string(), font_config("child", Some("middle"), "MIT"));
+
+        resolve_extends(&mut configs).unwrap();
+        let child = &configs["child"];
+        assert_eq!(child.version, "1.0");
+        assert_eq!(child.license, "MIT");
+        assert_eq!(child.fallback, vec![FallbackEntry::Local("other".to_string())]);
+        assert_eq!(configs["middle"].license, "OFL");
+    }
+
+    #[test]
+    fn test_fallback_entries() {
+        let entries: Vec<FallbackEntry> = serde_json::from_str(
+            r#"["Plangothic", {"url_pattern": "https://cdn.example.com/font?char={char}", "unicode_range": "U+30000-3134F"}]"#,
+        )
+        .unwrap();
+        assert_eq!(entries[0], FallbackEntry::Local("Plangothic".to_string()));
+        
+        let FallbackEntry::External(external) = &entries[1] else {
+            panic!("应解析为外部回退");
+        };
+        let matching = external.matching_codepoints(&[0x4E2D, 0x30000, 0x30001]);
+        assert_eq!(matching, vec![0x30000, 0x30001]);
+        assert_eq!(external.url_for(&matching), "https://cdn.example.com/font?char=196608,196609");
+        
+        let invalid = r#"{"url_pattern": "https://cdn.example.com/font", "unicode_range": "30000-3134F"}"#;
+        assert!(serde_json::from_str::<FallbackEntry>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_save_annotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = font_config("annotated", None, "OFL");
+        config.annotations.insert("environment".to_string(), "production".to_string());
+        config.save_to_dir(dir.path()).unwrap();
+        
+        let loaded = FontConfig::load_from_dir(dir.path()).unwrap();
+        assert_eq!(loaded.annotations, config.annotations);
+        
+        let content = std::fs::read_to_string(dir.path().join("config.json")).unwrap();
+        assert!(content.contains("\n    \"annotations\""));
+        assert!(!content.contains("\"name\""));
+    }
+
+    #[test]
+    fn test_validate_reports_duplicate_ids_and_missing_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let fonts_dir = dir.path().join("fonts");
+        for name in ["a", "b", "c"] {
+            let id = if name == "c" { "other" } else { "shared" };
+            std::fs::create_dir_all(fonts_dir.join(name)).unwrap();
+            font_config(id, None, "OFL").save_to_dir(&fonts_dir.join(name)).unwrap();
+        }
+        let config = AppConfig {
+            data_dir: dir.path().to_path_buf(),
+            static_dir: dir.path().join("static"),
+            ..AppConfig::default()
+        };
+        
+        assert_eq!(
+            config.validate(),
+            vec![
+                ConfigWarning::MissingDir(dir.path().join("static")),
+                ConfigWarning::DuplicateFontId {
+                    id: "shared".to_string(),
+                    dirs: vec![fonts_dir.join("a"), fonts_dir.join("b")],
+                },
+            ]
+        );
+        
+        std::fs::create_dir(dir.path().join("static")).unwrap();
+        std::fs::remove_dir_all(fonts_dir.join("b")).unwrap();
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn test_font_config_validate() {
+        let mut config = font_config("Valid-Font_1", None, "OFL");
+        config.files.push(FontFile {
+            name: "Regular".to_string(),
+            path: "Font.ttf".to_string(),
+            font_family: "Valid".to_string(),
+            sha256: None,
+        });
+        assert!(config.validate().is_empty());
+        
+        config.id = ".hidden".to_string();
+        config.files[0].path = "../other/Font.ttf".to_string();
+        config.overrides.push("Unknown-Block".to_string());
+        assert_eq!(config.validate().len(), 3);
+    }
+
+    #[test]
+    fn test_resolve_extends_cycle() {
+        let mut configs = HashMap::new();
+        configs.insert("a".to_string(), font_config("a", Some("b"), ""));
+        configs.insert("b".to_string(), font_config("b", Some("a"), ""));
+        assert!(resolve_extends(&mut configs).is_err());
+
+        let mut configs = HashMap::new();
+        configs.insert("a".to_string(), font_config("a", Some("missing"), ""));
+        assert!(resolve_extends(&mut configs).is_err());
+    }
+}
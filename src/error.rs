@@ -1,3 +1,4 @@
+use crate::utils::parse_accept_language;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -6,6 +7,36 @@ use axum::{
 use serde_json::json;
 use thiserror::Error;
 
+/// 外部回退字体地址的响应头
+pub const EXTERNAL_FALLBACK_HEADER: &str = "x-external-fallback-url";
+
+/// 错误信息使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorLanguage {
+    #[default]
+    Chinese,
+    English,
+}
+
+impl ErrorLanguage {
+    /// 按 `Accept-Language` 中权重最高的中文或英文标签选择，都没有时使用中文
+    pub fn from_accept_language(header: &str) -> Self {
+        parse_accept_language(header)
+            .into_iter()
+            .find_map(|tag| match tag.split('-').next() {
+                Some("zh") => Some(ErrorLanguage::Chinese),
+                Some("en") => Some(ErrorLanguage::English),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+tokio::task_local! {
+    /// 当前请求的错误信息语言，由 `middleware::error_language` 设置，未设置时使用中文
+    pub static ERROR_LANGUAGE: ErrorLanguage;
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("字体未找到: {0}")]
@@ -14,12 +45,50 @@ pub enum AppError {
     #[error("字符未找到: {0}")]
     CharacterNotFound(u32),
     
+    #[error("字体已存在: {0}")]
+    FontExists(String),
+    
+    #[error("字体未配置来源地址: {0}")]
+    SourceNotFound(String),
+    
+    /// 只有外部回退字体包含请求的字符，值为外部字体地址
+    #[error("字符由外部字体提供: {0}")]
+    ExternalFallback(String),
+    
+    /// 请求的字符分布在多个字体中，且都不是可以追加字形的TrueType字体，值为这些字体的ID
+    #[error("字符分布在多个字体中，但都不是非可变的TrueType字体，无法合并字形: {0}，请改用 format=collection")]
+    MergeUnsupported(String),
+    
+    #[error("缓存文件不存在: {0}")]
+    CacheNotFound(String),
+    
     #[error("配置错误: {0}")]
     ConfigError(String),
     
     #[error("字体处理错误: {0}")]
     FontProcessingError(String),
     
+    #[error("too few codepoints, minimum is {0}")]
+    TooFewCodepoints(usize),
+    
+    #[error("请求体超过 {0} 字节")]
+    PayloadTooLarge(usize),
+    
+    #[error("一次最多请求 {0} 个字体")]
+    TooManyFonts(usize),
+    
+    #[error("任务队列已满，请稍后重试")]
+    QueueFull,
+    
+    #[error("任务未找到: {0}")]
+    JobNotFound(String),
+    
+    #[error("未知的Schema类型: {0}")]
+    SchemaNotFound(String),
+    
+    #[error("未授权访问")]
+    Unauthorized,
+    
     #[error("IO错误: {0}")]
     IoError(#[from] std::io::Error),
     
@@ -30,16 +99,79 @@ pub enum AppError {
     InternalError(#[from] anyhow::Error),
 }
 
+impl AppError {
+    /// 英文错误信息，内部错误的详细内容不翻译
+    pub fn english_message(&self) -> String {
+        match self {
+            AppError::FontNotFound(id) => format!("Font not found: {}", id),
+            AppError::CharacterNotFound(codepoint) => format!("Character not found: {}", codepoint),
+            AppError::FontExists(id) => format!("Font already exists: {}", id),
+            AppError::SourceNotFound(id) => format!("No source URL configured for font: {}", id),
+            AppError::ExternalFallback(url) => format!("Characters are provided by an external font: {}", url),
+            AppError::MergeUnsupported(ids) => format!(
+                "Characters span multiple fonts and none is a static TrueType font to merge glyphs into: {}, use format=collection instead",
+                ids
+            ),
+            AppError::CacheNotFound(path) => format!("Cache file not found: {}", path),
+            AppError::ConfigError(message) => format!("Configuration error: {}", message),
+            AppError::FontProcessingError(message) => format!("Font processing error: {}", message),
+            AppError::TooFewCodepoints(_) => self.to_string(),
+            AppError::PayloadTooLarge(limit) => format!("Request body exceeds {} bytes", limit),
+            AppError::TooManyFonts(limit) => format!("At most {} fonts can be requested at once", limit),
+            AppError::QueueFull => "Job queue is full, please retry later".to_string(),
+            AppError::JobNotFound(id) => format!("Job not found: {}", id),
+            AppError::SchemaNotFound(name) => format!("Unknown schema type: {}", name),
+            AppError::Unauthorized => "Unauthorized".to_string(),
+            AppError::IoError(_) => "File system error".to_string(),
+            AppError::SerdeError(_) => "Malformed request".to_string(),
+            AppError::InternalError(_) => "Internal server error".to_string(),
+        }
+    }
+    
+    /// 返回给客户端的错误信息，不包含文件系统和内部错误的细节
+    fn message(&self, language: ErrorLanguage) -> String {
+        match (language, self) {
+            (ErrorLanguage::English, _) => self.english_message(),
+            (ErrorLanguage::Chinese, AppError::IoError(_)) => "文件系统错误".to_string(),
+            (ErrorLanguage::Chinese, AppError::SerdeError(_)) => "请求格式错误".to_string(),
+            (ErrorLanguage::Chinese, AppError::InternalError(_)) => "内部服务器错误".to_string(),
+            (ErrorLanguage::Chinese, _) => self.to_string(),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::FontNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::CharacterNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::ConfigError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            AppError::FontProcessingError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            AppError::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "文件系统错误".to_string()),
-            AppError::SerdeError(_) => (StatusCode::BAD_REQUEST, "请求格式错误".to_string()),
-            AppError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "内部服务器错误".to_string()),
+        let language = ERROR_LANGUAGE.try_with(|language| *language).unwrap_or_default();
+        let error_message = self.message(language);
+        if let AppError::ExternalFallback(url) = &self {
+            let body = Json(json!({
+                "error": error_message,
+                "url": url,
+            }));
+            return (StatusCode::NOT_FOUND, [(EXTERNAL_FALLBACK_HEADER, url.clone())], body).into_response();
+        }
+        
+        let status = match self {
+            AppError::FontNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::CharacterNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::FontExists(_) => StatusCode::CONFLICT,
+            AppError::SourceNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ExternalFallback(_) => StatusCode::NOT_FOUND,
+            AppError::CacheNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::MergeUnsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            AppError::ConfigError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::FontProcessingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::TooFewCodepoints(_) => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyFonts(_) => StatusCode::BAD_REQUEST,
+            AppError::QueueFull => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::JobNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::SchemaNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::SerdeError(_) => StatusCode::BAD_REQUEST,
+            AppError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         let body = Json(json!({
@@ -48,4 +180,4 @@ impl IntoResponse for AppError {
 
         (status, body).into_response()
     }
-}
\ No newline at end of file
+}
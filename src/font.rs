@@ -1,35 +1,544 @@
+use crate::{
+    cff,
+    render::{encode_png, GlyphOutline},
+};
 use anyhow::{anyhow, Result};
-use harfbuzz_rs_now::{Face, Owned};
+#[cfg(feature = "subsetting")]
+use harfbuzz_rs_now::{Blob, Face, HarfbuzzObject, Owned};
+#[cfg(feature = "subsetting")]
 use harfbuzz_rs_now::subset::Subset;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::Path;
+use std::sync::Arc;
+#[cfg(feature = "subsetting")]
+use std::ffi::{c_uint, c_void};
+#[cfg(feature = "subsetting")]
+use std::panic::AssertUnwindSafe;
+
+/// Unicode最大码点
+const MAX_CODEPOINT: u32 = 0x10FFFF;
+
+/// 子集化时去掉hinting的标志，对应 `HB_SUBSET_FLAGS_NO_HINTING`
+#[cfg(feature = "subsetting")]
+const HB_SUBSET_FLAGS_NO_HINTING: c_uint = 1;
+
+// harfbuzz_rs_now没有封装子集化标志，直接调用随其静态链接的HarfBuzz函数
+#[cfg(feature = "subsetting")]
+extern "C" {
+    fn hb_subset_input_get_flags(input: *mut c_void) -> c_uint;
+    fn hb_subset_input_set_flags(input: *mut c_void, value: c_uint);
+}
+
+/// OpenType name表中的一条记录
+#[derive(Debug, Clone, Serialize)]
+pub struct NameEntry {
+    pub platform_id: u16,
+    pub encoding_id: u16,
+    pub language_id: u16,
+    pub name_id: u16,
+    /// 无法解码为文本时为十六进制编码的原始数据
+    pub value: String,
+}
+
+/// 字体中的一个SFNT表
+#[derive(Debug, Clone, Serialize)]
+pub struct TableEntry {
+    pub tag: String,
+    /// 表的大小（字节）
+    pub size: u32,
+}
+
+/// 可变字体fvar表中的一个设计轴
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct VariableAxis {
+    pub tag: String,
+    /// name表中的轴名称，找不到时为空字符串
+    pub name: String,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
+
+/// GSUB表中的一条连字替换，均为原始字形ID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LigatureEntry {
+    /// 被替换的字形序列，第一个为覆盖表中的字形
+    pub sequence: Vec<u16>,
+    pub ligature: u16,
+}
+
+/// kern表中的一个字偶距调整，字形已通过cmap还原为码点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KernPair {
+    pub left_codepoint: u32,
+    pub right_codepoint: u32,
+    /// 调整值（字体单位）
+    pub value: i16,
+}
+
+/// OS/2表中与排版相关的度量信息，数值均以字体单位表示
+#[derive(Debug, Clone, Serialize)]
+pub struct Os2Metadata {
+    pub weight_class: u16,
+    pub width_class: u16,
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    pub units_per_em: u16,
+}
+
+/// 以字体单位和em比例同时表示的度量值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MetricValue {
+    pub units: i16,
+    /// `units / units_per_em`
+    pub em: f32,
+}
+
+/// CSS排版所需的垂直度量
+#[derive(Debug, Clone, Serialize)]
+pub struct FontMetrics {
+    pub units_per_em: u16,
+    pub ascender: MetricValue,
+    pub descender: MetricValue,
+    /// 默认行高，即 `ascender - descender + line_gap`
+    pub height: MetricValue,
+    pub line_gap: MetricValue,
+    /// 字体未提供时为 `null`
+    pub x_height: Option<MetricValue>,
+    pub cap_height: Option<MetricValue>,
+}
+
+/// WOFF2文件头中的字段，不需要解压即可读取
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Woff2Header {
+    /// 原始字体的sfnt版本，TrueType为 `0x00010000`，CFF为 `OTTO`
+    pub sf_version: String,
+    pub length: u32,
+    pub num_tables: u16,
+    pub total_sfnt_size: u32,
+    pub total_compressed_size: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+}
+
+impl Woff2Header {
+    /// WOFF2文件头的固定长度
+    pub const SIZE: usize = 48;
+    
+    /// 解析WOFF2文件头，数据不足或签名不是 `wOF2` 时返回错误
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < Self::SIZE {
+            return Err(anyhow!("WOFF2文件头不完整"));
+        }
+        if &data[..4] != b"wOF2" {
+            return Err(anyhow!("不是WOFF2文件"));
+        }
+        
+        let u16_at = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+        };
+        let flavor = &data[4..8];
+        let sf_version = if flavor.iter().all(u8::is_ascii_graphic) {
+            String::from_utf8_lossy(flavor).to_string()
+        } else {
+            format!("0x{:08X}", u32_at(4))
+        };
+        
+        Ok(Self {
+            sf_version,
+            length: u32_at(8),
+            num_tables: u16_at(12),
+            total_sfnt_size: u32_at(16),
+            total_compressed_size: u32_at(20),
+            major_version: u16_at(24),
+            minor_version: u16_at(26),
+        })
+    }
+}
+
+/// 字体的hinting类型，由轮廓格式和指令表判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum HintingType {
+    /// glyf轮廓且包含 `fpgm` 或 `prep` 指令表
+    TrueType,
+    /// CFF/CFF2轮廓，hint写在字形程序中
+    #[serde(rename = "CFF")]
+    Cff,
+    None,
+}
+
+/// 字体文件可疑但仍可使用的迹象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationWarning {
+    ZeroUnitsPerEm,
+    NoGlyphs,
+    MissingCmap,
+    MissingOs2,
+}
+
+impl std::fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ValidationWarning::ZeroUnitsPerEm => "unitsPerEm为0",
+            ValidationWarning::NoGlyphs => "字体不包含任何字形",
+            ValidationWarning::MissingCmap => "缺少cmap子表",
+            ValidationWarning::MissingOs2 => "缺少OS/2表",
+        };
+        f.write_str(message)
+    }
+}
 
 /// 字体处理器，负责字体分包和woff2生成
 pub struct FontProcessor {
-    font_data: Vec<u8>,
+    // 解析结构借用 `font_data`，字段按声明顺序析构，二者必须声明在 `font_data` 之前
     font_face: ttf_parser::Face<'static>,
+    #[cfg(feature = "subsetting")]
     harfbuzz_face: Owned<Face<'static>>,
+    family_name: String,
+    /// 加载时按 `文件大小 / 字形数量` 计算的平均字形大小（字节）
+    average_glyph_size: usize,
+    font_data: Arc<[u8]>,
+}
+
+/// 克隆只重新创建解析结构，字体数据在所有副本间共享，开销约为1微秒
+impl Clone for FontProcessor {
+    fn clone(&self) -> Self {
+        Self {
+            font_face: self.font_face.clone(),
+            #[cfg(feature = "subsetting")]
+            harfbuzz_face: harfbuzz_face(&self.font_data),
+            family_name: self.family_name.clone(),
+            average_glyph_size: self.average_glyph_size,
+            font_data: self.font_data.clone(),
+        }
+    }
+}
+
+/// 创建持有字体数据引用的HarfBuzz Face，数据在Face销毁时释放
+#[cfg(feature = "subsetting")]
+fn harfbuzz_face(font_data: &Arc<[u8]>) -> Owned<Face<'static>> {
+    Face::new(Blob::with_bytes_owned(font_data.clone(), |data| data), 0)
 }
 
 impl FontProcessor {
     pub fn new(font_path: &Path) -> Result<Self> {
-        let font_data = std::fs::read(font_path)?;
+        Self::from_bytes(std::fs::read(font_path)?)
+    }
+    
+    /// 基于内存中的字体数据创建处理器，数据在最后一个副本销毁时释放
+    pub fn from_bytes(font_data: Vec<u8>) -> Result<Self> {
+        Self::from_shared(Arc::from(font_data))
+    }
+    
+    /// 基于已加载的字体数据创建处理器，多个处理器可共享同一份数据
+    pub fn from_shared(font_data: Arc<[u8]>) -> Result<Self> {
+        // SAFETY: `Arc` 中的数据地址固定，`font_face` 先于同一结构中的 `font_data` 析构，
+        // 克隆出的副本也各自持有一份 `font_data`，借用不会比数据活得更久
+        let data: &'static [u8] = unsafe { &*Arc::as_ptr(&font_data) };
+        let font_face = ttf_parser::Face::parse(data, 0)
+            .map_err(|e| anyhow!("解析字体失败: {:?}", e))?;
         
-        // 使用 Box::leak 来获得 'static 生命周期
-        let static_data: &'static [u8] = Box::leak(font_data.clone().into_boxed_slice());
+        // 截断的字体能通过解析，但HarfBuzz子集化时会失败
+        if let Some(record) = font_face.raw_face().table_records.into_iter().find(|record| {
+            record.offset as usize + record.length as usize > font_data.len()
+        }) {
+            return Err(anyhow!("字体数据不完整: {} 表超出文件范围", record.tag));
+        }
         
-        let font_face = ttf_parser::Face::parse(static_data, 0)
-            .map_err(|e| anyhow!("解析字体失败: {:?}", e))?;
+        // 创建HarfBuzz Face用于字体子集化
+        #[cfg(feature = "subsetting")]
+        let harfbuzz_face = harfbuzz_face(&font_data);
         
-        // 创建HarfBuzz Face用于字体子集化，使用static_data避免生命周期问题
-        let harfbuzz_face = Face::from_bytes(static_data, 0);
+        let family_name = font_face
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == ttf_parser::name_id::FAMILY)
+            .find_map(|name| name.to_string())
+            .unwrap_or_default();
+        let average_glyph_size = font_data.len() / usize::from(font_face.number_of_glyphs().max(1));
             
         Ok(Self {
-            font_data,
             font_face,
+            #[cfg(feature = "subsetting")]
             harfbuzz_face,
+            family_name,
+            average_glyph_size,
+            font_data,
         })
     }
     
+    /// 字体文件的原始数据
+    pub fn font_data(&self) -> &[u8] {
+        &self.font_data
+    }
+    
+    /// 重新读取并解析字体文件，检查文件是否完整
+    pub fn verify_file(font_path: &Path) -> Result<()> {
+        let font_data = std::fs::read(font_path)?;
+        ttf_parser::Face::parse(&font_data, 0).map_err(|e| anyhow!("解析字体失败: {:?}", e))?;
+        Ok(())
+    }
+    
+    /// 检查常见的字体损坏迹象，只返回警告，字体仍然可以使用
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let face = &self.font_face;
+        let tables = face.tables();
+        let mut warnings = Vec::new();
+        
+        if face.units_per_em() == 0 {
+            warnings.push(ValidationWarning::ZeroUnitsPerEm);
+        }
+        if face.number_of_glyphs() == 0 {
+            warnings.push(ValidationWarning::NoGlyphs);
+        }
+        if tables.cmap.is_none_or(|cmap| cmap.subtables.is_empty()) {
+            warnings.push(ValidationWarning::MissingCmap);
+        }
+        if tables.os2.is_none() {
+            warnings.push(ValidationWarning::MissingOs2);
+        }
+        
+        warnings
+    }
+    
+    /// 检查name表中的字体族名（NameId 1或16）是否与配置声明的一致，不区分大小写
+    pub fn family_name_matches(&self, declared: &str) -> bool {
+        let declared = declared.to_lowercase();
+        self.font_face
+            .names()
+            .into_iter()
+            .filter(|name| {
+                name.name_id == ttf_parser::name_id::FAMILY
+                    || name.name_id == ttf_parser::name_id::TYPOGRAPHIC_FAMILY
+            })
+            .filter_map(|name| name.to_string())
+            .any(|family| family.to_lowercase() == declared)
+    }
+    
+    /// 平均每个字形占用的字节数，用于估算子集大小
+    pub fn average_glyph_size(&self) -> usize {
+        self.average_glyph_size
+    }
+    
+    /// 字体中的字形数量
+    pub fn number_of_glyphs(&self) -> u16 {
+        self.font_face.number_of_glyphs()
+    }
+    
+    /// 列出name表中的全部记录
+    pub fn name_table_entries(&self) -> Vec<NameEntry> {
+        self.font_face
+            .names()
+            .into_iter()
+            .map(|name| {
+                let value = name
+                    .to_string()
+                    .or_else(|| std::str::from_utf8(name.name).ok().map(|s| s.to_string()))
+                    .unwrap_or_else(|| name.name.iter().map(|b| format!("{:02x}", b)).collect());
+                NameEntry {
+                    platform_id: name.platform_id as u16,
+                    encoding_id: name.encoding_id,
+                    language_id: name.language_id,
+                    name_id: name.name_id,
+                    value,
+                }
+            })
+            .collect()
+    }
+    
+    /// 列出fvar表中的设计轴，非可变字体返回空列表
+    pub fn variable_axes(&self) -> Vec<VariableAxis> {
+        self.font_face
+            .variation_axes()
+            .into_iter()
+            .map(|axis| {
+                let name = self
+                    .font_face
+                    .names()
+                    .into_iter()
+                    .filter(|name| name.name_id == axis.name_id)
+                    .find_map(|name| name.to_string())
+                    .unwrap_or_default();
+                VariableAxis {
+                    tag: axis.tag.to_string(),
+                    name,
+                    min: axis.min_value,
+                    default: axis.def_value,
+                    max: axis.max_value,
+                }
+            })
+            .collect()
+    }
+    
+    /// 按表目录顺序列出字体包含的所有表
+    pub fn table_entries(&self) -> Vec<TableEntry> {
+        self.font_face
+            .raw_face()
+            .table_records
+            .into_iter()
+            .map(|record| TableEntry {
+                tag: record.tag.to_string(),
+                size: record.length,
+            })
+            .collect()
+    }
+    
+    /// 遍历GSUB表的所有查找，列出LookupType 4（连字）的替换规则
+    pub fn ligatures(&self) -> Vec<LigatureEntry> {
+        use ttf_parser::gsub::SubstitutionSubtable;
+        use ttf_parser::opentype_layout::Coverage;
+        
+        let Some(gsub) = self.font_face.tables().gsub else {
+            return Vec::new();
+        };
+        
+        let mut entries = Vec::new();
+        for lookup in gsub.lookups {
+            for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+                let SubstitutionSubtable::Ligature(substitution) = subtable else {
+                    continue;
+                };
+                
+                // 覆盖表按字形ID给出连字集合的下标
+                let first_glyphs: Vec<ttf_parser::GlyphId> = match substitution.coverage {
+                    Coverage::Format1 { glyphs } => glyphs.into_iter().collect(),
+                    Coverage::Format2 { records } => records
+                        .into_iter()
+                        .flat_map(|record| record.start.0..=record.end.0)
+                        .map(ttf_parser::GlyphId)
+                        .collect(),
+                };
+                for first in first_glyphs {
+                    let Some(set) = substitution
+                        .coverage
+                        .get(first)
+                        .and_then(|index| substitution.ligature_sets.get(index))
+                    else {
+                        continue;
+                    };
+                    for ligature in set {
+                        let mut sequence = vec![first.0];
+                        sequence.extend(ligature.components.into_iter().map(|glyph| glyph.0));
+                        entries.push(LigatureEntry {
+                            sequence,
+                            ligature: ligature.glyph.0,
+                        });
+                    }
+                }
+            }
+        }
+        
+        entries
+    }
+    
+    /// 读取OS/2表的字重、字宽和行高度量，字体没有OS/2表时返回 `None`
+    pub fn os2_metadata(&self) -> Option<Os2Metadata> {
+        self.font_face.tables().os2?;
+        Some(Os2Metadata {
+            weight_class: self.font_face.weight().to_number(),
+            width_class: self.font_face.width().to_number(),
+            ascender: self.font_face.ascender(),
+            descender: self.font_face.descender(),
+            line_gap: self.font_face.line_gap(),
+            units_per_em: self.font_face.units_per_em(),
+        })
+    }
+    
+    /// 读取排版度量，同时给出相对于UPM的比例
+    pub fn metrics(&self) -> FontMetrics {
+        let face = &self.font_face;
+        let units_per_em = face.units_per_em();
+        let metric = |units: i16| MetricValue {
+            units,
+            em: f32::from(units) / f32::from(units_per_em.max(1)),
+        };
+        
+        FontMetrics {
+            units_per_em,
+            ascender: metric(face.ascender()),
+            descender: metric(face.descender()),
+            height: metric(face.height()),
+            line_gap: metric(face.line_gap()),
+            x_height: face.x_height().map(metric),
+            cap_height: face.capital_height().map(metric),
+        }
+    }
+    
+    /// 是否为非可变的TrueType轮廓字体，只有这类字体的子集可以追加其他字体的字形
+    pub fn can_append_glyphs(&self) -> bool {
+        let tables = self.font_face.tables();
+        tables.glyf.is_some() && tables.fvar.is_none()
+    }
+    
+    /// 判断字体使用的hinting类型
+    pub fn hinting_type(&self) -> HintingType {
+        let tables = self.font_face.tables();
+        if tables.cff.is_some() || tables.cff2.is_some() {
+            return HintingType::Cff;
+        }
+        
+        let raw_face = self.font_face.raw_face();
+        let has_instructions = [b"fpgm", b"prep"]
+            .into_iter()
+            .any(|tag| raw_face.table(ttf_parser::Tag::from_bytes(tag)).is_some());
+        if tables.glyf.is_some() && has_instructions {
+            HintingType::TrueType
+        } else {
+            HintingType::None
+        }
+    }
+    
+    /// CFF字体的Private DICT，TrueType字体返回 `None`
+    pub fn cff_private_dict(&self) -> Option<HashMap<String, serde_json::Value>> {
+        let cff = self.font_face.raw_face().table(ttf_parser::Tag::from_bytes(b"CFF "))?;
+        cff::private_dict(cff)
+    }
+    
+    /// 字体是否包含彩色字形表（COLR、CBDT、CBLC或sbix）
+    pub fn has_color_glyphs(&self) -> bool {
+        let raw_face = self.font_face.raw_face();
+        [b"COLR", b"CBDT", b"CBLC", b"sbix"]
+            .into_iter()
+            .any(|tag| raw_face.table(ttf_parser::Tag::from_bytes(tag)).is_some())
+    }
+    
+    /// 把字符的字形缩放到 `size`×`size` 的方框中，水平居中，上下以升部和降部为界
+    ///
+    /// 字体不包含该字符时返回 `None`，没有轮廓的字形（如空格）返回空轮廓
+    fn glyph_outline(&self, codepoint: u32, size: u32) -> Option<GlyphOutline> {
+        let glyph_id = self.font_face.glyph_index(char::from_u32(codepoint)?)?;
+        let face = &self.font_face;
+        let line_height = (i32::from(face.ascender()) - i32::from(face.descender())).max(1) as f32;
+        let scale = size as f32 / line_height;
+        let advance = f32::from(face.glyph_hor_advance(glyph_id).unwrap_or(face.units_per_em()));
+        
+        let mut outline = GlyphOutline::new(
+            scale,
+            (size as f32 - advance * scale) / 2.0,
+            f32::from(face.ascender()) * scale,
+        );
+        face.outline_glyph(glyph_id, &mut outline);
+        Some(outline)
+    }
+    
+    /// 生成字符字形的SVG图像，字体不包含该字符时返回 `None`
+    pub fn glyph_svg(&self, codepoint: u32, size: u32) -> Option<String> {
+        self.glyph_outline(codepoint, size).map(|outline| outline.to_svg(size))
+    }
+    
+    /// 把字符字形渲染为黑色、透明背景的PNG图像
+    pub fn glyph_png(&self, codepoint: u32, size: u32) -> Result<Vec<u8>> {
+        let outline = self
+            .glyph_outline(codepoint, size)
+            .ok_or_else(|| anyhow!("字体不包含字符: U+{:04X}", codepoint))?;
+        encode_png(&outline.rasterize(size), size)
+    }
+    
     /// 检查字体是否包含指定字符
     pub fn contains_char(&self, codepoint: u32) -> bool {
         if let Some(ch) = char::from_u32(codepoint) {
@@ -48,8 +557,94 @@ impl FontProcessor {
             .collect()
     }
     
+    /// 列出kern表所有水平字偶距子表中的调整，没有kern表时返回空列表
+    ///
+    /// 只展开格式0（有序字形对）子表，按类别定义的格式2和格式3子表无法枚举，会被跳过。
+    /// 多个码点映射到同一字形时取最小的码点，没有码点的字形对被忽略
+    pub fn full_kern_table(&self) -> Vec<KernPair> {
+        use ttf_parser::kern::Format;
+        
+        let Some(kern) = self.font_face.tables().kern else {
+            return Vec::new();
+        };
+        
+        // cmap_entries按码点升序排列，只保留每个字形的第一个码点
+        let mut codepoints_by_glyph = HashMap::new();
+        for (cp, glyph_id) in self.cmap_entries() {
+            codepoints_by_glyph.entry(glyph_id).or_insert(cp);
+        }
+        
+        let mut pairs = Vec::new();
+        for subtable in kern.subtables {
+            if !subtable.horizontal || subtable.has_cross_stream {
+                continue;
+            }
+            let Format::Format0(subtable) = subtable.format else {
+                log::debug!("跳过无法枚举的kern子表: {}", self.family_name);
+                continue;
+            };
+            for pair in subtable.pairs {
+                let (Some(&left), Some(&right)) = (
+                    codepoints_by_glyph.get(&pair.left().0),
+                    codepoints_by_glyph.get(&pair.right().0),
+                ) else {
+                    continue;
+                };
+                pairs.push(KernPair {
+                    left_codepoint: left,
+                    right_codepoint: right,
+                    value: pair.value,
+                });
+            }
+        }
+        
+        pairs
+    }
+    
+    /// 列出cmap中每个码点对应的字形ID，按码点升序排列
+    pub fn cmap_entries(&self) -> Vec<(u32, u16)> {
+        self.get_all_codepoints()
+            .into_iter()
+            .filter_map(|cp| {
+                let glyph_id = self.font_face.glyph_index(char::from_u32(cp)?)?;
+                Some((cp, glyph_id.0))
+            })
+            .collect()
+    }
+    
+    /// 获取字体支持的全部码点，按升序排列
+    pub fn get_all_codepoints(&self) -> Vec<u32> {
+        let mut codepoints = BTreeSet::new();
+        if let Some(cmap) = self.font_face.tables().cmap {
+            for subtable in cmap.subtables {
+                if subtable.is_unicode() {
+                    // 损坏的cmap可能声明超出Unicode范围的区间，先排除再查字形
+                    subtable.codepoints(|cp| {
+                        if cp <= MAX_CODEPOINT && self.contains_char(cp) {
+                            codepoints.insert(cp);
+                        }
+                    });
+                }
+            }
+        }
+        codepoints.into_iter().collect()
+    }
+    
     /// 生成包含指定字符的子集字体
     pub fn subset_font(&self, codepoints: &[u32]) -> Result<Vec<u8>> {
+        self.subset_with_hints(codepoints, true)
+    }
+    
+    /// 生成子集字体，`preserve_hints` 为 `false` 时去掉TrueType指令和CFF hint
+    #[tracing::instrument(
+        skip(self, codepoints),
+        fields(
+            font_family = %self.family_name,
+            codepoint_count = codepoints.len(),
+            ttf_size = tracing::field::Empty,
+        )
+    )]
+    pub fn subset_with_hints(&self, codepoints: &[u32], preserve_hints: bool) -> Result<Vec<u8>> {
         // 过滤出字体实际包含的字符
         let available_chars: Vec<char> = codepoints
             .iter()
@@ -66,28 +661,61 @@ impl FontProcessor {
             return Err(anyhow!("字体不包含任何请求的字符"));
         }
         
+        // HarfBuzz子集化不一定能完整保留彩色字形
+        if self.has_color_glyphs() {
+            log::warn!("正在对包含彩色字形的字体进行子集化，生成的字体可能丢失颜色");
+        }
+        
         // 使用harfbuzz进行字体子集化
-        self.create_subset(&available_chars)
+        let ttf_data = self.create_subset(&available_chars, preserve_hints)?;
+        tracing::Span::current().record("ttf_size", ttf_data.len());
+        Ok(ttf_data)
     }
     
-    fn create_subset(&self, chars: &[char]) -> Result<Vec<u8>> {
+    #[cfg(feature = "subsetting")]
+    fn create_subset(&self, chars: &[char], preserve_hints: bool) -> Result<Vec<u8>> {
         // 使用HarfBuzz进行字体子集化
         let subset_runner = Subset::new();
         subset_runner.clear_drop_table();
         subset_runner.adjust_layout();
+        if !preserve_hints {
+            let input = subset_runner.as_raw().cast();
+            // SAFETY: `input` 指向 `subset_runner` 持有的有效子集化参数
+            unsafe {
+                hb_subset_input_set_flags(input, hb_subset_input_get_flags(input) | HB_SUBSET_FLAGS_NO_HINTING);
+            }
+        }
         
         // 将字符转换为Unicode码点
         let codepoints: Vec<u32> = chars.iter().map(|&c| c as u32).collect();
         subset_runner.add_chars(&codepoints);
         
-        // 执行子集化
-        let subset_face = subset_runner.run_subset(&self.harfbuzz_face);
+        // 执行子集化，HarfBuzz失败时 `run_subset` 会panic
+        let subset_face = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            subset_runner.run_subset(&self.harfbuzz_face)
+        }))
+        .map_err(|_| anyhow!("HarfBuzz子集化失败"))?;
         let subset_data = subset_face.face_data();
         
         Ok(subset_data.get_data().to_vec())
     }
     
+    #[cfg(not(feature = "subsetting"))]
+    fn create_subset(&self, _chars: &[char], _preserve_hints: bool) -> Result<Vec<u8>> {
+        Err(anyhow!("subsetting not compiled in"))
+    }
+    
+    /// 当前可输出的字体格式
+    pub fn output_formats() -> BTreeMap<&'static str, bool> {
+        BTreeMap::from([
+            ("woff2", cfg!(feature = "subsetting")),
+            ("woff", false),
+            ("ttf", cfg!(feature = "subsetting")),
+        ])
+    }
+    
     /// 将TTF数据转换为WOFF2格式
+    #[cfg(feature = "subsetting")]
     pub fn ttf_to_woff2(ttf_data: &[u8]) -> Result<Vec<u8>> {
         // 使用woff库进行TTF到WOFF2转换
         match woff::version2::compress(ttf_data, String::new(), 1, true) {
@@ -99,9 +727,51 @@ impl FontProcessor {
         }
     }
     
+    #[cfg(not(feature = "subsetting"))]
+    pub fn ttf_to_woff2(_ttf_data: &[u8]) -> Result<Vec<u8>> {
+        Err(anyhow!("subsetting not compiled in"))
+    }
+    
+    /// 将WOFF2数据还原为TTF格式
+    #[cfg(feature = "subsetting")]
+    pub fn woff2_to_ttf(woff2_data: &[u8]) -> Result<Vec<u8>> {
+        // 数据无效时woff库会panic
+        std::panic::catch_unwind(|| woff::version2::decompress(woff2_data))
+            .ok()
+            .flatten()
+            .ok_or_else(|| anyhow!("WOFF2解压失败"))
+    }
+    
+    #[cfg(not(feature = "subsetting"))]
+    pub fn woff2_to_ttf(_woff2_data: &[u8]) -> Result<Vec<u8>> {
+        Err(anyhow!("subsetting not compiled in"))
+    }
+    
+    /// 读取生成的子集（TTF或WOFF2）中的字形数量，无法解析时返回 `None`
+    pub fn subset_glyph_count(font_data: &[u8]) -> Option<u16> {
+        let ttf_data;
+        let data = if font_data.starts_with(b"wOF2") {
+            ttf_data = Self::woff2_to_ttf(font_data).ok()?;
+            &ttf_data[..]
+        } else {
+            font_data
+        };
+        ttf_parser::Face::parse(data, 0).ok().map(|face| face.number_of_glyphs())
+    }
+    
     /// 生成包含指定字符的WOFF2字体
+    #[tracing::instrument(
+        skip(self, codepoints),
+        fields(
+            font_family = %self.family_name,
+            codepoint_count = codepoints.len(),
+            woff2_size = tracing::field::Empty,
+        )
+    )]
     pub fn generate_woff2(&self, codepoints: &[u32]) -> Result<Vec<u8>> {
         let ttf_data = self.subset_font(codepoints)?;
-        Self::ttf_to_woff2(&ttf_data)
+        let woff2_data = Self::ttf_to_woff2(&ttf_data)?;
+        tracing::Span::current().record("woff2_size", woff2_data.len());
+        Ok(woff2_data)
     }
-}
\ No newline at end of file
+}
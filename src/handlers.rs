@@ -1,30 +1,295 @@
-use crate::{error::AppError, service::FontInfo, utils::parse_codepoints, AppState};
+use crate::{
+    config::{AppConfig, CharsetPreset, FontConfig},
+    error::AppError,
+    font::{FontMetrics, FontProcessor, HintingType, KernPair, LigatureEntry, NameEntry, Os2Metadata, TableEntry, VariableAxis, Woff2Header},
+    service::{CacheCompactReport, CacheManifestEntry, CmapPage, CoverageRow, EmojiCoverage, FontBatchSummary, FontFileInfo, FontInfo, FontVerifyReport, GlyphPage},
+    subsetter::JobStatus,
+    middleware,
+    utils::{build_zip, content_digest, escape_html, parse_accept_language, parse_codepoints_dense, parse_codepoints_from_json, parse_codepoints_range},
+    AppState,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use axum::{
-    extract::{Query, State},
-    http::{header, HeaderMap},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::from_fn_with_state,
     response::{Html, IntoResponse, Response},
-    Json,
+    routing::post,
+    Json, Router,
 };
+use regex::Regex;
+use tower_http::limit::RequestBodyLimitLayer;
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct FontQuery {
+    /// `format=collection` 时可用逗号分隔多个字体ID，`format=ttf` 时返回TTF子集
     pub id: String,
     #[serde(rename = "char")]
     pub chars: String,
+    #[serde(default)]
+    pub format: Option<String>,
+    /// 为 `true` 时沿回退链查找请求的字符，字符分布在多个字体中时合并各字体的字形
+    #[serde(default)]
+    pub include_fallback: bool,
+    /// 为 `true` 时在 `X-Tofu-Codepoints` 响应头中列出所有字体都不包含的码点
+    #[serde(default)]
+    pub warn_tofu: bool,
+    /// 为 `false` 时返回去掉hinting的WOFF2子集
+    #[serde(default = "default_hints")]
+    pub hints: bool,
 }
 
-#[derive(Deserialize)]
+fn default_hints() -> bool {
+    true
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct GenerateQuery {
     pub id: Option<String>,
+    #[serde(default, rename = "char")]
+    pub chars: String,
+    /// 内置字符集名称，如 `gb2312-frequent-3500`，与 `char` 同时提供时合并
+    #[serde(default)]
+    pub charset: Option<String>,
+    /// 为 `true` 时放入后台队列并立即返回任务ID
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub localized: bool,
+    /// JSONP回调函数名，设置时返回 `callback(...);` 形式的JavaScript
+    #[serde(default)]
+    pub callback: Option<String>,
+    /// 为 `true` 时按 `font_family` 合并字体，同一字体族的配置列在 `variants` 中
+    #[serde(default)]
+    pub deduplicate: bool,
+    /// 排序方式，未设置时按字体ID排序
+    #[serde(default)]
+    pub sort: Option<SortOrder>,
+}
+
+/// 字体列表的排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// 按请求语言的字体名称排序，没有名称时使用 `font_family`
+    Alpha,
+    /// 按第一个字体文件的字形数量从多到少排序
+    Coverage,
+    /// 按字体ID排序
+    Id,
+}
+
+#[derive(Deserialize)]
+pub struct EstimateQuery {
+    pub id: String,
     #[serde(rename = "char")]
     pub chars: String,
 }
 
+#[derive(Deserialize)]
+pub struct CoverageQuery {
+    #[serde(rename = "char")]
+    pub chars: String,
+}
+
+#[derive(Deserialize)]
+pub struct ByCharQuery {
+    #[serde(rename = "char")]
+    pub chars: String,
+}
+
+#[derive(Deserialize)]
+pub struct GlyphsQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_per_page")]
+    pub per_page: usize,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    1000
+}
+
+#[derive(Deserialize)]
+pub struct MostUsedQuery {
+    #[serde(default = "default_most_used")]
+    pub n: usize,
+}
+
+fn default_most_used() -> usize {
+    10
+}
+
+#[derive(Deserialize)]
+pub struct RenameRequest {
+    pub new_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct GlyphPngQuery {
+    #[serde(default = "default_glyph_size")]
+    pub size: u32,
+}
+
+fn default_glyph_size() -> u32 {
+    64
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    pub text: Option<String>,
+}
+
+/// 默认的预览文字
+const DEFAULT_PREVIEW_TEXT: &str = "天地玄黄，宇宙洪荒。";
+
+/// 预览页面的内容安全策略，只允许内联样式和data URI字体
+const PREVIEW_CSP: &str = "default-src 'self'; style-src 'unsafe-inline'; font-src data:";
+
+/// 缓存清单的文件名
+const CACHE_MANIFEST_FILENAME: &str = "manifest.json";
+
+/// 列出缺字码点的响应头，码点以逗号分隔
+pub const TOFU_HEADER: &str = "x-tofu-codepoints";
+
+/// RFC 9530 定义的响应体摘要头
+pub const CONTENT_DIGEST: &str = "content-digest";
+
+/// 返回的子集中包含的字形数量
+pub const GLYPH_COUNT_HEADER: &str = "x-font-glyph-count";
+
+/// WOFF2子集内容的BLAKE3哈希，可用于 `/api/v1/font/:id/subset/:hash` 固定链接
+pub const SUBSET_HASH_HEADER: &str = "x-subset-hash";
+
+/// JSONP回调函数名只允许JavaScript标识符，防止注入脚本
+static JSONP_CALLBACK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-zA-Z_$][a-zA-Z0-9_$]*$").unwrap());
+
+/// 单页最多返回的码点数量
+const MAX_PER_PAGE: usize = 10000;
+
+/// `format=collection` 一次最多请求的字体数量
+pub const MAX_COLLECTION_FONTS: usize = 16;
+
+/// 字形PNG的边长范围（像素）
+const MIN_GLYPH_SIZE: u32 = 8;
+const MAX_GLYPH_SIZE: u32 = 1024;
+
+/// 解析 `char` 参数，支持十进制码点列表、JSON数组 `[20013,25991]`、`U+4E00-9FFF` 范围写法和连续的 `U+4E2DU+6587`
+fn parse_chars(chars: &str) -> Result<Vec<u32>, AppError> {
+    parse_codepoints_from_json(chars)
+        .or_else(|_| parse_codepoints_range(chars))
+        .or_else(|_| parse_codepoints_dense(chars))
+        .map_err(|_| AppError::ConfigError("无效的字符码点格式".to_string()))
+}
+
 /// GET /api/v1/list - 列出所有可用字体
-pub async fn list_fonts(State(service): State<AppState>) -> Result<Json<Vec<FontInfo>>, AppError> {
-    let fonts = service.list_fonts().await;
+pub async fn list_fonts(
+    Query(params): Query<ListQuery>,
+    State(service): State<AppState>,
+    request_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(callback) = &params.callback {
+        if !JSONP_CALLBACK.is_match(callback) {
+            return Err(AppError::ConfigError(format!("无效的JSONP回调函数名: {}", callback)));
+        }
+    }
+    
+    let mut fonts = service.list_fonts().await;
+    
+    // 取权重最高的中文语言标签，没有时使用简体
+    let language = request_headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(parse_accept_language)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|tag| tag == "zh" || tag.starts_with("zh-"))
+        .unwrap_or_else(|| "zh-hans".to_string());
+    
+    if params.localized {
+        for font in &mut fonts {
+            let display_name = match font.name.take() {
+                Some(name) => name.for_language(&language).to_string(),
+                None => font.font_family.clone(),
+            };
+            font.display_name = Some(display_name);
+        }
+    }
+    
+    if params.deduplicate {
+        fonts = FontInfo::group_by_family(fonts);
+    }
+    
+    match params.sort {
+        Some(SortOrder::Alpha) => {
+            // `localized=true` 时名称已移到 `display_name`
+            let sort_name = |font: &FontInfo| match (&font.display_name, &font.name) {
+                (Some(display_name), _) => display_name.clone(),
+                (None, Some(name)) => name.for_language(&language).to_string(),
+                (None, None) => font.font_family.clone(),
+            };
+            fonts.sort_by_cached_key(|font| (sort_name(font), font.id.clone()));
+        }
+        Some(SortOrder::Coverage) => fonts.sort_by(|a, b| b.glyph_count.cmp(&a.glyph_count).then_with(|| a.id.cmp(&b.id))),
+        Some(SortOrder::Id) | None => fonts.sort_by(|a, b| a.id.cmp(&b.id)),
+    }
+    
+    match params.callback {
+        Some(callback) => {
+            let body = format!("{}({});", callback, serde_json::to_string(&fonts)?);
+            Ok(([(header::CONTENT_TYPE, "application/javascript")], body).into_response())
+        }
+        None => Ok(Json(fonts).into_response()),
+    }
+}
+
+/// GET /api/v1/font/by-char - 列出包含指定码点的字体
+pub async fn fonts_by_char(
+    Query(params): Query<ByCharQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontInfo>>, AppError> {
+    let codepoints = parse_chars(&params.chars)?;
+    let [codepoint] = codepoints[..] else {
+        return Err(AppError::ConfigError("只能查询单个码点".to_string()));
+    };
+    
+    let fonts = service.fonts_covering_codepoint(codepoint).await;
+    Ok(Json(fonts))
+}
+
+/// GET /api/v1/font/least-covered - 按覆盖率从低到高列出每个字体对请求字符的覆盖百分比，只计算字体自身文件
+pub async fn least_covered_fonts(
+    Query(params): Query<CoverageQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<serde_json::Value>>, AppError> {
+    let codepoints = parse_chars(&params.chars)?;
+    if codepoints.is_empty() {
+        return Err(AppError::ConfigError("字符码点不能为空".to_string()));
+    }
+    
+    let fonts = service
+        .least_covered_for(&codepoints)
+        .await
+        .into_iter()
+        .map(|(font_id, coverage)| serde_json::json!({ "font_id": font_id, "coverage": coverage }))
+        .collect();
     Ok(Json(fonts))
 }
 
@@ -32,24 +297,519 @@ pub async fn list_fonts(State(service): State<AppState>) -> Result<Json<Vec<Font
 pub async fn get_font(
     Query(params): Query<FontQuery>,
     State(service): State<AppState>,
+    request_headers: HeaderMap,
 ) -> Result<Response, AppError> {
-    let codepoints = parse_codepoints(&params.chars)
-        .map_err(|_| AppError::ConfigError("无效的字符码点格式".to_string()))?;
+    let codepoints = parse_chars(&params.chars)?;
     
     if codepoints.is_empty() {
         return Err(AppError::ConfigError("字符码点不能为空".to_string()));
     }
     
-    let woff2_data = service.get_cached_font(&params.id, &codepoints).await?;
+    let min_codepoints = service.config().min_codepoints_per_request;
+    if codepoints.len() < min_codepoints {
+        return Err(AppError::TooFewCodepoints(min_codepoints));
+    }
+    
+    service.record_request(&codepoints);
+    match params.format.as_deref() {
+        None | Some("ttf") => {}
+        Some("collection") => {
+            return font_collection(&service, &params.id, &codepoints, &request_headers).await;
+        }
+        Some(format) => return Err(AppError::ConfigError(format!("不支持的格式: {}", format))),
+    }
+    
+    // 单个字体包含全部可用字符时直接返回它的子集，否则把各回退字体的字形合并到一个字体中
+    let mut merged_sources = Vec::new();
+    let font_id = if params.include_fallback {
+        let mut sources = service.fallback_sources(&params.id, &codepoints).await?;
+        match sources.len() {
+            0 => return Err(AppError::CharacterNotFound(codepoints[0])),
+            1 => sources.remove(0).0,
+            _ => {
+                merged_sources = sources;
+                params.id.clone()
+            }
+        }
+    } else {
+        params.id.clone()
+    };
+    let merged = !merged_sources.is_empty();
+    let (cached, content_type) = match (params.format.as_deref(), params.hints) {
+        (Some("ttf"), false) => {
+            return Err(AppError::ConfigError("format=ttf 暂不支持 hints=false".to_string()));
+        }
+        (Some("ttf"), true) if merged => (service.get_merged_font(&merged_sources, true, true).await?, "font/ttf"),
+        (Some("ttf"), true) => (service.get_cached_ttf(&font_id, &codepoints).await?, "font/ttf"),
+        (_, hints) if merged => (
+            service.get_merged_font(&merged_sources, false, hints).await?,
+            "application/font-woff2",
+        ),
+        (_, true) => (service.get_cached_font(&font_id, &codepoints).await?, "application/font-woff2"),
+        (_, false) => (
+            service.get_cached_unhinted_font(&font_id, &codepoints).await?,
+            "application/font-woff2",
+        ),
+    };
+    if merged {
+        for (source_id, _) in &merged_sources {
+            service.record_font_served(source_id);
+        }
+    } else {
+        service.record_font_served(&font_id);
+    }
     
     let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "application/font-woff2".parse().unwrap());
+    headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
     headers.insert(
         header::CACHE_CONTROL,
         "public, max-age=31536000, immutable".parse().unwrap(),
     );
+    if params.warn_tofu {
+        let tofu = service.detect_tofu(&params.id, &codepoints).await;
+        if !tofu.is_empty() {
+            let list = tofu.iter().map(|cp| cp.to_string()).collect::<Vec<_>>().join(",");
+            headers.insert(TOFU_HEADER, list.parse().unwrap());
+        }
+    }
+    
+    if let Some(modified) = cached.modified {
+        headers.insert(
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(modified).parse().unwrap(),
+        );
+        if is_not_modified(&request_headers, modified) {
+            return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+        }
+    }
+    
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(cached.data.len()));
+    headers.insert(CONTENT_DIGEST, cached.content_digest.parse().unwrap());
+    if let Some(glyph_count) = cached.glyph_count {
+        headers.insert(GLYPH_COUNT_HEADER, HeaderValue::from(glyph_count));
+    }
+    // 合并的字体不写入缓存，没有固定链接
+    if content_type != "font/ttf" && !merged {
+        headers.insert(SUBSET_HASH_HEADER, cached.hash.parse().unwrap());
+    }
+    Ok((headers, cached.data).into_response())
+}
+
+/// GET /api/v1/font/:id/subset/:hash - 按WOFF2内容的BLAKE3哈希返回缓存子集，内容不随字体更新而改变
+pub async fn get_subset_by_hash(
+    Path((id, hash)): Path<(String, String)>,
+    State(service): State<AppState>,
+) -> Result<Response, AppError> {
+    let cached = service.get_subset_by_hash(&id, &hash).await?;
+    
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/font-woff2"));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(cached.data.len()));
+    headers.insert(CONTENT_DIGEST, cached.content_digest.parse().unwrap());
+    Ok((headers, cached.data).into_response())
+}
+
+/// 将多个字体的子集合并返回，客户端接受 `application/zip` 时返回ZIP，否则返回内嵌base64的JSON
+async fn font_collection(
+    service: &AppState,
+    ids: &str,
+    codepoints: &[u32],
+    request_headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    // 重复的ID只生成一次，否则ZIP中会出现同名文件
+    let mut font_ids: Vec<&str> = Vec::new();
+    for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        if !font_ids.contains(&id) {
+            font_ids.push(id);
+        }
+    }
+    if font_ids.len() > MAX_COLLECTION_FONTS {
+        return Err(AppError::TooManyFonts(MAX_COLLECTION_FONTS));
+    }
+    
+    let mut entries = Vec::new();
+    for id in font_ids {
+        let cached = service.get_cached_font(id, codepoints).await?;
+        service.record_font_served(id);
+        entries.push((id.to_string(), cached.data));
+    }
+    
+    let accepts_zip = request_headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/zip"));
+    if accepts_zip {
+        let files: Vec<(String, Vec<u8>)> = entries
+            .into_iter()
+            .map(|(id, data)| (format!("{}.woff2", id), data))
+            .collect();
+        let archive = build_zip(&files)?;
+        return Ok(([(header::CONTENT_TYPE, "application/zip")], archive).into_response());
+    }
+    
+    let fonts: Vec<serde_json::Value> = entries
+        .into_iter()
+        .map(|(id, data)| serde_json::json!({ "id": id, "woff2": BASE64.encode(data) }))
+        .collect();
+    let body = serde_json::to_vec(&serde_json::json!({ "fonts": fonts }))?;
+    Ok(([(header::CONTENT_TYPE, "application/font-collection+json")], body).into_response())
+}
+
+/// GET /api/v1/font/:id/glyphs - 分页列出字体支持的码点
+pub async fn list_glyphs(
+    Path(id): Path<String>,
+    Query(params): Query<GlyphsQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<GlyphPage>, AppError> {
+    let per_page = params.per_page.min(MAX_PER_PAGE);
+    let glyphs = service.list_glyphs(&id, params.page, per_page).await?;
+    Ok(Json(glyphs))
+}
+
+/// GET /api/v1/font/:id/glyph/:codepoint/png - 把单个字形渲染为PNG缩略图
+pub async fn glyph_png(
+    Path((id, codepoint)): Path<(String, u32)>,
+    Query(params): Query<GlyphPngQuery>,
+    State(service): State<AppState>,
+) -> Result<Response, AppError> {
+    let size = params.size.clamp(MIN_GLYPH_SIZE, MAX_GLYPH_SIZE);
+    let png = service.glyph_png(&id, codepoint, size).await?;
+    
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("image/png")),
+            (header::CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600")),
+        ],
+        png,
+    )
+        .into_response())
+}
+
+/// GET /api/v1/font/:id/emoji-coverage - 统计字体支持的emoji码点
+pub async fn emoji_coverage(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<EmojiCoverage>, AppError> {
+    let coverage = service.emoji_coverage(&id).await?;
+    Ok(Json(coverage))
+}
+
+/// GET /api/v1/font/:id/subset-count - 统计字体已缓存的子集数量
+pub async fn count_subsets(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let count = service.count_subsets(&id).await?;
+    Ok(Json(serde_json::json!({ "font_id": id, "count": count })))
+}
+
+/// GET /api/v1/font/:id/ranges - 以CSS `unicode-range` 格式返回字体覆盖的码点范围
+pub async fn font_ranges(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<String>>, AppError> {
+    let ranges = service.font_ranges(&id).await?;
+    Ok(Json(ranges))
+}
+
+/// 根据 `If-Modified-Since` 判断客户端缓存是否仍然有效
+fn is_not_modified(request_headers: &HeaderMap, modified: SystemTime) -> bool {
+    let Some(since) = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+    else {
+        return false;
+    };
+    
+    // HTTP日期只精确到秒
+    let modified_secs = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(u64::MAX);
+    let since_secs = since
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    modified_secs <= since_secs
+}
+
+/// GET /api/v1/font/:id/subset-preview - 返回内嵌WOFF2字体的独立预览页面
+pub async fn subset_preview(
+    Path(id): Path<String>,
+    Query(params): Query<PreviewQuery>,
+    State(service): State<AppState>,
+) -> Result<Response, AppError> {
+    let text = params
+        .text
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PREVIEW_TEXT.to_string());
+    
+    let mut codepoints: Vec<u32> = text.chars().map(|c| c as u32).collect();
+    codepoints.sort_unstable();
+    codepoints.dedup();
+    
+    let cached = service.get_cached_font(&id, &codepoints).await?;
+    let font_base64 = BASE64.encode(&cached.data);
+    let font_id = escape_html(&id);
+    let text = escape_html(&text);
+    
+    let html = Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<title>{font_id}</title>
+<style>
+@font-face {{
+  font-family: "preview-font";
+  src: url("data:font/woff2;base64,{font_base64}") format("woff2");
+}}
+body {{
+  font-family: "preview-font", serif;
+  font-size: 48px;
+  margin: 40px;
+}}
+</style>
+</head>
+<body>
+<p>{text}</p>
+</body>
+</html>
+"#
+    ));
+    
+    Ok(([(header::CONTENT_SECURITY_POLICY, PREVIEW_CSP)], html).into_response())
+}
+
+/// GET /api/v1/font/:id/source - 重定向到字体的上游项目
+pub async fn font_source(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Response, AppError> {
+    let source_url = service
+        .font_source_url(&id)
+        .await?
+        .ok_or(AppError::SourceNotFound(id))?;
+    
+    Ok((StatusCode::FOUND, [(header::LOCATION, source_url)]).into_response())
+}
+
+/// GET /api/v1/font/:id/annotate - 获取字体的运维标签
+pub async fn font_annotations(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let annotations = service.font_annotations(&id).await?;
+    Ok(Json(annotations))
+}
+
+/// POST /api/v1/font/:id/annotate - 合并修改字体的运维标签，值为 `null` 时删除该键
+pub async fn annotate_font(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+    Json(patch): Json<HashMap<String, Option<String>>>,
+) -> Result<Json<HashMap<String, String>>, AppError> {
+    let annotations = service.update_annotations(&id, patch).await?;
+    Ok(Json(annotations))
+}
+
+/// POST /api/v1/font/:id/rename - 修改字体ID，新ID已存在时返回409
+pub async fn rename_font(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+    Json(request): Json<RenameRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    service.rename_font(&id, &request.new_id).await?;
+    Ok(Json(serde_json::json!({ "old_id": id, "new_id": request.new_id })))
+}
+
+/// POST /api/v1/font/validate-config - 检查提交的 `config.json`，不安装字体
+pub async fn validate_font_config(
+    State(service): State<AppState>,
+    body: Bytes,
+) -> Json<serde_json::Value> {
+    let errors = match serde_json::from_slice::<FontConfig>(&body) {
+        Ok(font_config) => service.validate_font_config(&font_config),
+        Err(e) => vec![format!("配置格式错误: {}", e)],
+    };
     
-    Ok((headers, woff2_data).into_response())
+    if errors.is_empty() {
+        Json(serde_json::json!({ "valid": true }))
+    } else {
+        Json(serde_json::json!({ "valid": false, "errors": errors }))
+    }
+}
+
+/// GET /api/v1/font/:id/nametable - 列出每个字体文件的name表
+pub async fn name_table(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Vec<NameEntry>>>>, AppError> {
+    let entries = service.name_table(&id).await?;
+    Ok(Json(entries))
+}
+
+/// GET /api/v1/font/:id/os2 - 列出每个字体文件的OS/2度量信息
+pub async fn os2_metadata(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Option<Os2Metadata>>>>, AppError> {
+    let metadata = service.os2_metadata(&id).await?;
+    Ok(Json(metadata))
+}
+
+/// GET /api/v1/font/:id/cmap - 分页列出每个字体文件中码点到字形ID的映射
+pub async fn cmap(
+    Path(id): Path<String>,
+    Query(params): Query<GlyphsQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<CmapPage>>>, AppError> {
+    if params.page == 0 || params.per_page == 0 {
+        return Err(AppError::ConfigError("分页参数必须大于0".to_string()));
+    }
+    let per_page = params.per_page.min(MAX_PER_PAGE);
+    let pages = service.cmap(&id, params.page, per_page).await?;
+    Ok(Json(pages))
+}
+
+/// GET /api/v1/font/:id/hinting - 列出每个字体文件的hinting类型
+pub async fn hinting_type(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<HintingType>>>, AppError> {
+    let hinting = service.hinting_types(&id).await?;
+    Ok(Json(hinting))
+}
+
+/// GET /api/v1/font/:id/cff-dict - 列出每个字体文件CFF表的Private DICT，TrueType字体为 `null`
+pub async fn cff_private_dict(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Option<HashMap<String, serde_json::Value>>>>>, AppError> {
+    let dicts = service.cff_private_dicts(&id).await?;
+    Ok(Json(dicts))
+}
+
+/// GET /api/v1/font/:id/axes - 列出每个字体文件fvar表中的可变轴，非可变字体为空数组
+pub async fn variable_axes(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Vec<VariableAxis>>>>, AppError> {
+    let axes = service.variable_axes(&id).await?;
+    Ok(Json(axes))
+}
+
+/// GET /api/v1/font/:id/kerning - 列出每个字体文件kern表中的全部字偶距，没有kern表时为空数组
+pub async fn kerning(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Vec<KernPair>>>>, AppError> {
+    let kerning = service.kern_tables(&id).await?;
+    Ok(Json(kerning))
+}
+
+/// GET /api/v1/font/:id/tables - 列出每个字体文件包含的SFNT表及其大小
+pub async fn table_entries(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Vec<TableEntry>>>>, AppError> {
+    let tables = service.table_entries(&id).await?;
+    Ok(Json(tables))
+}
+
+/// GET /api/v1/font/:id/ligatures - 列出每个字体文件GSUB表中的连字替换
+pub async fn ligatures(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<Vec<LigatureEntry>>>>, AppError> {
+    let ligatures = service.ligatures(&id).await?;
+    Ok(Json(ligatures))
+}
+
+/// GET /api/v1/font/:id/metrics - 返回每个字体文件的排版度量
+pub async fn metrics(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<FontFileInfo<FontMetrics>>>, AppError> {
+    let metrics = service.metrics(&id).await?;
+    Ok(Json(metrics))
+}
+
+/// GET /api/v1/font/:id/coverage-heatmap - 按256码点一格返回CJK统一表意文字各区块的覆盖率
+pub async fn coverage_heatmap(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Json<Vec<CoverageRow>>, AppError> {
+    let rows = service.coverage_heatmap(&id).await?;
+    Ok(Json(rows))
+}
+
+/// GET /api/v1/font/:id/woff2-header - 读取已缓存子集的WOFF2文件头
+pub async fn woff2_header(
+    Path(id): Path<String>,
+    Query(params): Query<ByCharQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<Woff2Header>, AppError> {
+    let codepoints = parse_chars(&params.chars)?;
+    let header = service.woff2_header(&id, &codepoints).await?;
+    Ok(Json(header))
+}
+
+/// GET /api/v1/font/estimate - 估算子集的WOFF2大小，误差约±30%
+pub async fn estimate_subset_size(
+    Query(params): Query<EstimateQuery>,
+    State(service): State<AppState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let codepoints = parse_chars(&params.chars)?;
+    let estimated_bytes = service.estimate_subset_size(&params.id, &codepoints).await?;
+    Ok(Json(serde_json::json!({ "estimated_bytes": estimated_bytes })))
+}
+
+/// GET /api/v1/font/most-used - 列出自启动以来请求次数最多的字体，用于决定预热哪些字体
+pub async fn most_used_fonts(
+    Query(params): Query<MostUsedQuery>,
+    State(service): State<AppState>,
+) -> Json<Vec<serde_json::Value>> {
+    let fonts = service
+        .most_used_fonts(params.n)
+        .into_iter()
+        .map(|(font_id, requests)| serde_json::json!({ "font_id": font_id, "requests": requests }))
+        .collect();
+    Json(fonts)
+}
+
+/// GET /api/v1/font/formats - 列出当前可用的输出格式
+pub async fn list_formats() -> Json<BTreeMap<&'static str, bool>> {
+    Json(FontProcessor::output_formats())
+}
+
+/// GET /api/v1/version - 返回服务版本、构建时的Git提交和构建时间
+pub async fn version() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_hash": env!("GIT_HASH"),
+        "build_timestamp": env!("BUILD_TIMESTAMP"),
+    }))
+}
+
+/// GET /api/v1/schema/:type - 返回配置和请求参数类型的JSON Schema
+///
+/// 可用的类型为 `font-config`、`app-config`、`font-info`、`generate-query` 和 `font-query`
+pub async fn json_schema(Path(schema_type): Path<String>) -> Result<Json<schemars::schema::RootSchema>, AppError> {
+    let schema = match schema_type.as_str() {
+        "font-config" => schemars::schema_for!(FontConfig),
+        "app-config" => schemars::schema_for!(AppConfig),
+        "font-info" => schemars::schema_for!(FontInfo),
+        "generate-query" => schemars::schema_for!(GenerateQuery),
+        "font-query" => schemars::schema_for!(FontQuery),
+        _ => return Err(AppError::SchemaNotFound(schema_type)),
+    };
+    Ok(Json(schema))
 }
 
 /// POST /api/v1/generate - 重新生成字体文件
@@ -57,13 +817,43 @@ pub async fn generate_font(
     Query(params): Query<GenerateQuery>,
     State(service): State<AppState>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let codepoints = parse_codepoints(&params.chars)
-        .map_err(|_| AppError::ConfigError("无效的字符码点格式".to_string()))?;
+    let mut codepoints = if params.chars.is_empty() && params.charset.is_some() {
+        Vec::new()
+    } else {
+        parse_chars(&params.chars)?
+    };
+    if let Some(name) = &params.charset {
+        let preset: CharsetPreset = name
+            .parse()
+            .map_err(|e: anyhow::Error| AppError::ConfigError(e.to_string()))?;
+        let mut merged: BTreeSet<u32> = codepoints.into_iter().collect();
+        merged.extend(preset.codepoints());
+        codepoints = merged.into_iter().collect();
+    }
     
     if codepoints.is_empty() {
         return Err(AppError::ConfigError("字符码点不能为空".to_string()));
     }
     
+    let min_codepoints = service.config().min_codepoints_per_request;
+    if codepoints.len() < min_codepoints {
+        return Err(AppError::TooFewCodepoints(min_codepoints));
+    }
+    
+    if params.run_async {
+        let job_id = service
+            .subsetter()
+            .submit(params.id.clone(), codepoints.clone())
+            .await?;
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "job_id": job_id,
+            "status": JobStatus::Queued.name(),
+            "font_id": params.id,
+            "characters": codepoints.len()
+        })));
+    }
+    
     service
         .regenerate_font(params.id.as_deref(), &codepoints)
         .await?;
@@ -76,7 +866,173 @@ pub async fn generate_font(
     })))
 }
 
+/// 批量上传接口的路由，需要管理员令牌，使用单独的大小限制，压缩的请求体按解压后的大小检查
+///
+/// 令牌校验在最外层，未授权的请求不会被读取和解压
+pub fn upload_routes(state: AppState, max_upload_bytes: usize) -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/font/batch-upload", post(batch_upload))
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_upload_bytes))
+        .layer(from_fn_with_state(max_upload_bytes, middleware::decompress_request))
+        .route_layer(from_fn_with_state(state, middleware::require_admin))
+}
+
+/// POST /api/v1/font/batch-upload - 通过ZIP压缩包批量安装字体
+pub async fn batch_upload(
+    State(service): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<FontBatchSummary>, AppError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::ConfigError(format!("无效的上传请求: {}", e)))?
+    {
+        let is_zip = field
+            .file_name()
+            .map(|name| name.to_lowercase().ends_with(".zip"))
+            .unwrap_or(false);
+        if !is_zip {
+            continue;
+        }
+        
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::ConfigError(format!("读取上传文件失败: {}", e)))?;
+        let summary = service.install_font_archive(data.to_vec()).await?;
+        return Ok(Json(summary));
+    }
+    
+    Err(AppError::ConfigError("缺少ZIP文件".to_string()))
+}
+
+/// POST /api/v1/cache/export-manifest - 导出缓存清单到静态目录
+pub async fn export_cache_manifest(
+    State(service): State<AppState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let output_path = service.config().static_dir.join(CACHE_MANIFEST_FILENAME);
+    service.export_cache_manifest(&output_path).await?;
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "path": format!("/static/{}", CACHE_MANIFEST_FILENAME)
+    })))
+}
+
+/// POST /api/v1/font/:id/rebuild-cache - 在后台重新生成字体的所有缓存子集
+pub async fn rebuild_cache(
+    Path(id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let jobs = service.rebuild_cache(&id).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "font_id": id, "jobs": jobs })),
+    ))
+}
+
+/// POST /api/v1/admin/verify - 校验所有字体文件的完整性
+pub async fn verify_fonts(State(service): State<AppState>) -> Result<Json<FontVerifyReport>, AppError> {
+    let report = service.verify_all_fonts().await?;
+    Ok(Json(report))
+}
+
+/// POST /api/v1/admin/compact-cache - 用硬链接合并内容相同的缓存文件
+pub async fn compact_cache(State(service): State<AppState>) -> Result<Json<CacheCompactReport>, AppError> {
+    let report = service.compact_cache().await?;
+    Ok(Json(report))
+}
+
+/// GET /api/v1/job/:job_id - 查询后台任务状态，完成后直接返回WOFF2字体
+pub async fn job_status(
+    Path(job_id): Path<String>,
+    State(service): State<AppState>,
+) -> Result<Response, AppError> {
+    let status = service
+        .subsetter()
+        .status(&job_id)
+        .await
+        .ok_or_else(|| AppError::JobNotFound(job_id.clone()))?;
+    
+    let response = match status {
+        JobStatus::Done(woff2_data) => {
+            let content_length = HeaderValue::from(woff2_data.len());
+            let digest = content_digest(&woff2_data).parse().unwrap();
+            (
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_static("application/font-woff2")),
+                    (header::CONTENT_LENGTH, content_length),
+                    (HeaderName::from_static(CONTENT_DIGEST), digest),
+                ],
+                woff2_data,
+            )
+                .into_response()
+        }
+        JobStatus::Failed(error) => Json(serde_json::json!({
+            "job_id": job_id,
+            "status": "failed",
+            "error": error
+        }))
+        .into_response(),
+        pending => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({
+                "job_id": job_id,
+                "status": pending.name()
+            })),
+        )
+            .into_response(),
+    };
+    
+    Ok(response)
+}
+
 /// GET / - 主页
 pub async fn index() -> Html<&'static str> {
     Html(include_str!("../index.html"))
-}
\ No newline at end of file
+}
+
+/// GET /static/ - 按字体分组列出所有缓存的WOFF2文件
+pub async fn static_index_handler(State(service): State<AppState>) -> Html<String> {
+    let mut groups: BTreeMap<String, Vec<CacheManifestEntry>> = BTreeMap::new();
+    for entry in service.cache_entries() {
+        groups.entry(entry.font_id.clone()).or_default().push(entry);
+    }
+    
+    let mut sections = String::new();
+    for (font_id, entries) in &groups {
+        let total: u64 = entries.iter().map(|e| e.size).sum();
+        sections.push_str(&format!(
+            "<h2>{} <small>({} 个文件，{} 字节)</small></h2>\n<ul>\n",
+            escape_html(font_id),
+            entries.len(),
+            total
+        ));
+        for entry in entries {
+            let path = escape_html(&entry.path);
+            sections.push_str(&format!(
+                "<li><a href=\"/static/{path}\">{path}</a> {} 字节</li>\n",
+                entry.size
+            ));
+        }
+        sections.push_str("</ul>\n");
+    }
+    if groups.is_empty() {
+        sections.push_str("<p>暂无缓存文件</p>\n");
+    }
+    
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<title>缓存文件索引</title>
+</head>
+<body>
+<h1>缓存文件索引</h1>
+{sections}</body>
+</html>
+"#
+    ))
+}
@@ -0,0 +1,22 @@
+pub mod backend;
+pub mod batcher;
+pub mod cff;
+pub mod charsets;
+pub mod config;
+pub mod error;
+pub mod font;
+pub mod handlers;
+pub mod merge;
+pub mod middleware;
+pub mod render;
+pub mod service;
+pub mod subsetter;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod utils;
+
+use std::sync::Arc;
+
+use backend::FontBackend;
+
+pub type AppState = Arc<dyn FontBackend>;
@@ -1,48 +1,220 @@
 use axum::{
+    middleware::{from_fn, from_fn_with_state},
     routing::{get, post},
     Router,
 };
-use std::{net::SocketAddr, sync::Arc};
-use tower_http::{cors::CorsLayer, services::ServeDir};
-
-mod config;
-mod error;
-mod font;
-mod handlers;
-mod service;
-mod utils;
-
-use config::AppConfig;
+use axum_server::tls_rustls::RustlsConfig;
+use hyper_util::{
+    rt::{TokioExecutor, TokioTimer},
+    server::conn::auto::Builder,
+};
+use std::{net::SocketAddr, path::Path, sync::Arc, time::Duration};
+use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer, services::ServeDir};
 
-use service::FontService;
+use webfont_zh::{
+    config::{AppConfig, TlsConfig}, handlers, AppState, middleware, service::FontService, subsetter::BackgroundSubsetter,
+};
 
-pub type AppState = Arc<FontService>;
+#[cfg(all(feature = "console", not(tokio_unstable)))]
+compile_error!("`console` 特性需要使用 RUSTFLAGS=\"--cfg tokio_unstable\" 编译");
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+/// 初始化日志，启用 `console` 特性并设置 `WEBFONT_TOKIO_CONSOLE` 时改用tokio-console
+fn init_logging() {
+    #[cfg(feature = "console")]
+    if std::env::var_os("WEBFONT_TOKIO_CONSOLE").is_some() {
+        console_subscriber::init();
+        return;
+    }
+    
     env_logger::init();
+}
+
+fn main() -> anyhow::Result<()> {
+    init_logging();
     
     let config = AppConfig::load()?;
+    for warning in config.validate() {
+        log::warn!("配置检查: {}", warning);
+    }
+    
+    // 未配置线程数时与 `#[tokio::main]` 一样使用全部CPU核心
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = config.worker_threads {
+        log::info!("工作线程数: {}", worker_threads);
+        builder.worker_threads(worker_threads);
+    }
+    builder.enable_all().build()?.block_on(run(config))
+}
+
+async fn run(config: AppConfig) -> anyhow::Result<()> {
+    let max_body_bytes = config.max_body_bytes;
+    let max_upload_bytes = config.max_upload_bytes;
+    let bind_address = config.bind_address;
+    let serve_static_index = config.serve_static_index;
     let font_service = Arc::new(FontService::new(config).await?);
+    font_service.print_startup_summary().await;
+    BackgroundSubsetter::start(&font_service);
+    
+    let warmup_service = font_service.clone();
+    tokio::spawn(async move { warmup_service.warm_up().await });
+    
+    let state: AppState = font_service.clone();
+    
+    // 管理接口需要访问令牌
+    let admin_routes = Router::new()
+        .route(
+            "/api/v1/cache/export-manifest",
+            post(handlers::export_cache_manifest),
+        )
+        .route("/api/v1/admin/verify", post(handlers::verify_fonts))
+        .route("/api/v1/admin/compact-cache", post(handlers::compact_cache))
+        .route("/api/v1/font/:id/rebuild-cache", post(handlers::rebuild_cache))
+        .route("/api/v1/font/:id/annotate", post(handlers::annotate_font))
+        .route("/api/v1/font/:id/rename", post(handlers::rename_font))
+        .route_layer(from_fn_with_state(state.clone(), middleware::require_admin));
+    
+    // 静态文件服务作为嵌套路由的回退，`/static/` 索引页可以单独注册
+    let static_files = Router::new().fallback_service(ServeDir::new("data/static"));
+    let static_routes = if serve_static_index {
+        Router::new().route("/static/", get(handlers::static_index_handler))
+    } else {
+        Router::new()
+    }
+    .nest("/static", static_files);
     
     let app = Router::new()
         .route("/", get(handlers::index))
+        .route("/api/v1/version", get(handlers::version))
+        .route("/api/v1/schema/:type", get(handlers::json_schema))
         .route("/api/v1/list", get(handlers::list_fonts))
         .route("/api/v1/font", get(handlers::get_font))
+        .route("/api/v1/font/formats", get(handlers::list_formats))
+        .route("/api/v1/font/by-char", get(handlers::fonts_by_char))
+        .route("/api/v1/font/most-used", get(handlers::most_used_fonts))
+        .route("/api/v1/font/least-covered", get(handlers::least_covered_fonts))
+        .route("/api/v1/font/validate-config", post(handlers::validate_font_config))
+        .route("/api/v1/font/estimate", get(handlers::estimate_subset_size))
+        .route("/api/v1/font/:id/glyphs", get(handlers::list_glyphs))
+        .route("/api/v1/font/:id/glyph/:codepoint/png", get(handlers::glyph_png))
+        .route("/api/v1/font/:id/ranges", get(handlers::font_ranges))
+        .route("/api/v1/font/:id/emoji-coverage", get(handlers::emoji_coverage))
+        .route("/api/v1/font/:id/subset-count", get(handlers::count_subsets))
+        .route("/api/v1/font/:id/nametable", get(handlers::name_table))
+        .route("/api/v1/font/:id/os2", get(handlers::os2_metadata))
+        .route("/api/v1/font/:id/cmap", get(handlers::cmap))
+        .route("/api/v1/font/:id/hinting", get(handlers::hinting_type))
+        .route("/api/v1/font/:id/cff-dict", get(handlers::cff_private_dict))
+        .route("/api/v1/font/:id/axes", get(handlers::variable_axes))
+        .route("/api/v1/font/:id/kerning", get(handlers::kerning))
+        .route("/api/v1/font/:id/tables", get(handlers::table_entries))
+        .route("/api/v1/font/:id/ligatures", get(handlers::ligatures))
+        .route("/api/v1/font/:id/metrics", get(handlers::metrics))
+        .route("/api/v1/font/:id/coverage-heatmap", get(handlers::coverage_heatmap))
+        .route("/api/v1/font/:id/woff2-header", get(handlers::woff2_header))
+        .route("/api/v1/font/:id/source", get(handlers::font_source))
+        .route("/api/v1/font/:id/annotate", get(handlers::font_annotations))
+        .route("/api/v1/font/:id/subset-preview", get(handlers::subset_preview))
+        .route("/api/v1/font/:id/subset/:hash", get(handlers::get_subset_by_hash))
         .route("/api/v1/generate", post(handlers::generate_font))
-        .nest_service("/static", ServeDir::new("data/static"))
+        .route("/api/v1/job/:job_id", get(handlers::job_status))
+        .merge(admin_routes)
+        .route_layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .merge(handlers::upload_routes(state.clone(), max_upload_bytes))
+        .merge(static_routes)
+        .layer(from_fn(middleware::error_language))
         .layer(CorsLayer::permissive())
-        .with_state(font_service);
+        .layer(from_fn(middleware::request_id))
+        .with_state(state);
+    // 审计日志放在最外层，记录包括静态文件在内的全部请求
+    let app = match font_service.config().request_log_path.as_deref() {
+        Some(log_path) => {
+            log::info!("请求审计日志写入 {}.YYYY-MM-DD", log_path.display());
+            app.layer(from_fn_with_state(Arc::<Path>::from(log_path), middleware::request_log))
+        }
+        None => app,
+    };
 
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "8000".to_string())
         .parse::<u16>()
         .unwrap_or(8000);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    log::info!("服务器启动在 {}", addr);
+    let addr = SocketAddr::from((bind_address, port));
+    
+    let keep_alive_timeout = Duration::from_secs(font_service.config().keep_alive_timeout_secs);
+    match font_service.config().tls.clone() {
+        Some(tls) => serve_tls(addr, app, &tls, keep_alive_timeout).await?,
+        None => serve_http(addr, app, keep_alive_timeout).await?,
+    }
     
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // 所有请求处理完毕后再停止后台任务
+    font_service.shutdown();
+    log::info!("服务器已关闭");
     
     Ok(())
+}
+
+/// 使用HTTP提供服务
+async fn serve_http(addr: SocketAddr, app: Router, keep_alive_timeout: Duration) -> anyhow::Result<()> {
+    log::info!("服务器启动在 {}", addr);
+    let mut server = axum_server::bind(addr).handle(shutdown_handle());
+    set_keep_alive_timeout(server.http_builder(), keep_alive_timeout);
+    server.serve(app.into_make_service()).await?;
+    Ok(())
+}
+
+/// 使用rustls提供HTTPS服务
+async fn serve_tls(addr: SocketAddr, app: Router, tls: &TlsConfig, keep_alive_timeout: Duration) -> anyhow::Result<()> {
+    let rustls_config = RustlsConfig::from_pem_file(&tls.cert_file, &tls.key_file).await?;
+    
+    log::info!("HTTPS服务器启动在 {}", addr);
+    let mut server = axum_server::bind_rustls(addr, rustls_config).handle(shutdown_handle());
+    set_keep_alive_timeout(server.http_builder(), keep_alive_timeout);
+    server.serve(app.into_make_service()).await?;
+    Ok(())
+}
+
+/// 收到停止信号后等待所有连接处理完毕再退出
+fn shutdown_handle() -> axum_server::Handle {
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+    handle
+}
+
+/// 限制HTTP/1连接等待请求头的时间，包括keep-alive连接在两个请求之间的空闲时间
+fn set_keep_alive_timeout(builder: &mut Builder<TokioExecutor>, timeout: Duration) {
+    builder.http1().timer(TokioTimer::new()).header_read_timeout(timeout);
+}
+
+/// 等待 Ctrl+C 或 SIGTERM 信号
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                log::error!("注册SIGTERM处理失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    
+    log::info!("收到关闭信号，等待进行中的请求完成");
 }
\ No newline at end of file
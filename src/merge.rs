@@ -0,0 +1,564 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use ttf_parser::{Face, GlyphId, OutlineBuilder, RawFace, Tag};
+
+/// 合并后删除的表，内容按字形编号记录且无法为追加的字形补全
+const DROPPED_TABLES: [&[u8; 4]; 7] = [b"hdmx", b"LTSH", b"VDMX", b"DSIG", b"vhea", b"vmtx", b"VORG"];
+
+/// 由合并过程重新生成的表
+const REBUILT_TABLES: [&[u8; 4]; 8] = [b"head", b"maxp", b"hhea", b"hmtx", b"loca", b"glyf", b"cmap", b"post"];
+
+/// 三次曲线近似为二次曲线时允许的误差（基础字体的字体单位）
+const CUBIC_TOLERANCE: f32 = 0.5;
+/// 一段三次曲线最多拆分的二次曲线数
+const MAX_CUBIC_SPLITS: usize = 16;
+
+/// 坐标的取值范围，保证相邻点的差值不超出i16
+const MAX_COORDINATE: f32 = 16383.0;
+
+/// glyf简单字形的点标志
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+
+/// `head.checkSumAdjustment` 使用的常量
+const CHECKSUM_MAGIC: u32 = 0xB1B0_AFBA;
+
+/// 把其他字体中码点对应的字形追加到TrueType子集末尾，返回新的TTF数据
+///
+/// `sources` 中的字体可以是TrueType或CFF轮廓，字形按基础字体的 `unitsPerEm` 缩放后
+/// 重新编码为不含指令的简单字形，三次曲线近似为二次曲线。靠后的来源覆盖基础字体中
+/// 相同码点的映射，来源中不存在的码点被忽略。基础字体必须是非可变的TrueType字体
+pub fn append_glyphs(base: &[u8], sources: &[(&[u8], &[u32])]) -> Result<Vec<u8>> {
+    let raw_face = RawFace::parse(base, 0).map_err(|e| anyhow!("无法解析基础字体: {}", e))?;
+    let table = |tag: &[u8; 4]| raw_face.table(Tag::from_bytes(tag));
+    if table(b"fvar").is_some() {
+        return Err(anyhow!("不支持向可变字体追加字形"));
+    }
+    let (Some(head), Some(maxp), Some(hhea), Some(hmtx), Some(loca), Some(glyf)) = (
+        table(b"head"),
+        table(b"maxp"),
+        table(b"hhea"),
+        table(b"hmtx"),
+        table(b"loca"),
+        table(b"glyf"),
+    ) else {
+        return Err(anyhow!("基础字体不是TrueType轮廓"));
+    };
+    if head.len() < 54 || maxp.len() < 32 || hhea.len() < 36 {
+        return Err(anyhow!("基础字体的head、maxp或hhea表不完整"));
+    }
+
+    let units_per_em = read_u16(head, 18);
+    let base_glyphs = usize::from(read_u16(maxp, 4));
+    let mut glyphs = read_glyf(glyf, loca, base_glyphs, read_u16(head, 50) == 1)?;
+    let mut metrics = read_hmtx(hmtx, usize::from(read_u16(hhea, 34)), base_glyphs)?;
+    let mut mappings = unicode_mappings(base)?;
+
+    let mut appended = Vec::new();
+    for &(data, codepoints) in sources {
+        let face = Face::parse(data, 0).map_err(|e| anyhow!("无法解析来源字体: {}", e))?;
+        let scale = f32::from(units_per_em) / f32::from(face.units_per_em());
+        // 同一字形对应多个码点时只追加一次
+        let mut added: HashMap<GlyphId, u16> = HashMap::new();
+        for &codepoint in codepoints {
+            let Some(glyph_id) = char::from_u32(codepoint).and_then(|c| face.glyph_index(c)) else {
+                continue;
+            };
+            let new_id = match added.get(&glyph_id) {
+                Some(&new_id) => new_id,
+                None => {
+                    let new_id = u16::try_from(glyphs.len())
+                        .ok()
+                        .filter(|&id| id < u16::MAX)
+                        .ok_or_else(|| anyhow!("合并后的字形数量超过65535"))?;
+                    let glyph = AppendedGlyph::new(&face, glyph_id, scale);
+                    glyphs.push(glyph.data.clone());
+                    metrics.push((glyph.advance, glyph.bbox.map_or(0, |bbox| bbox[0])));
+                    appended.push(glyph);
+                    added.insert(glyph_id, new_id);
+                    new_id
+                }
+            };
+            mappings.insert(codepoint, new_id);
+        }
+    }
+
+    let (glyf, loca) = build_glyf(&glyphs);
+    let mut tables: BTreeMap<[u8; 4], Vec<u8>> = BTreeMap::new();
+    for record in raw_face.table_records {
+        let tag = record.tag.to_bytes();
+        if DROPPED_TABLES.contains(&&tag) || REBUILT_TABLES.contains(&&tag) {
+            continue;
+        }
+        if let Some(data) = raw_face.table(record.tag) {
+            tables.insert(tag, data.to_vec());
+        }
+    }
+    tables.insert(*b"head", build_head(head, &appended));
+    tables.insert(*b"maxp", build_maxp(maxp, glyphs.len(), &appended));
+    tables.insert(*b"hhea", build_hhea(hhea, &metrics, &appended));
+    tables.insert(*b"hmtx", metrics.iter().flat_map(|&(advance, lsb)| [advance.to_be_bytes(), lsb.to_be_bytes()]).flatten().collect());
+    tables.insert(*b"loca", loca);
+    tables.insert(*b"glyf", glyf);
+    tables.insert(*b"cmap", build_cmap(&mappings));
+    if let Some(post) = table(b"post").filter(|post| post.len() >= 32) {
+        // 版本3不含字形名称，不需要为追加的字形补充
+        let mut post = post[..32].to_vec();
+        post[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+        tables.insert(*b"post", post);
+    }
+
+    Ok(build_sfnt(read_u32(base, 0), &tables))
+}
+
+/// 从其他字体复制并重新编码的字形
+struct AppendedGlyph {
+    data: Vec<u8>,
+    advance: u16,
+    /// xMin、yMin、xMax、yMax，空字形为 `None`
+    bbox: Option<[i16; 4]>,
+    points: u16,
+    contours: u16,
+}
+
+impl AppendedGlyph {
+    fn new(face: &Face, glyph_id: GlyphId, scale: f32) -> Self {
+        let advance = face.glyph_hor_advance(glyph_id).unwrap_or(0);
+        let advance = (f32::from(advance) * scale).round().clamp(0.0, f32::from(u16::MAX)) as u16;
+
+        let mut builder = ContourBuilder {
+            scale,
+            contours: Vec::new(),
+        };
+        face.outline_glyph(glyph_id, &mut builder);
+        let contours: Vec<Vec<(i16, i16, bool)>> = builder
+            .contours
+            .into_iter()
+            .filter(|contour| contour.len() > 1)
+            .map(|contour| contour.into_iter().map(|(x, y, on_curve)| (round_coordinate(x), round_coordinate(y), on_curve)).collect())
+            .collect();
+        if contours.is_empty() {
+            return Self {
+                data: Vec::new(),
+                advance,
+                bbox: None,
+                points: 0,
+                contours: 0,
+            };
+        }
+
+        let points = contours.iter().flatten();
+        let bbox = [
+            points.clone().map(|p| p.0).min().unwrap_or(0),
+            points.clone().map(|p| p.1).min().unwrap_or(0),
+            points.clone().map(|p| p.0).max().unwrap_or(0),
+            points.clone().map(|p| p.1).max().unwrap_or(0),
+        ];
+        let point_count = points.count();
+        Self {
+            data: encode_simple_glyph(&contours, bbox),
+            advance,
+            bbox: Some(bbox),
+            points: u16::try_from(point_count).unwrap_or(u16::MAX),
+            contours: u16::try_from(contours.len()).unwrap_or(u16::MAX),
+        }
+    }
+}
+
+/// 收集缩放后的轮廓点，三次曲线拆分为二次曲线
+struct ContourBuilder {
+    scale: f32,
+    contours: Vec<Vec<(f32, f32, bool)>>,
+}
+
+impl ContourBuilder {
+    fn push(&mut self, x: f32, y: f32, on_curve: bool) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push((x * self.scale, y * self.scale, on_curve));
+        }
+    }
+
+    fn current(&self) -> (f32, f32) {
+        self.contours
+            .last()
+            .and_then(|contour| contour.last())
+            .map_or((0.0, 0.0), |&(x, y, _)| (x, y))
+    }
+}
+
+impl OutlineBuilder for ContourBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.contours.push(Vec::new());
+        self.push(x, y, true);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(x, y, true);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.push(x1, y1, false);
+        self.push(x, y, true);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let s = self.scale;
+        let p0 = self.current();
+        let (p1, p2, p3) = ((x1 * s, y1 * s), (x2 * s, y2 * s), (x * s, y * s));
+        let cubic = |t: f32| {
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            (a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1)
+        };
+        let derivative = |t: f32| {
+            let u = 1.0 - t;
+            let (a, b, c) = (3.0 * u * u, 6.0 * u * t, 3.0 * t * t);
+            (
+                a * (p1.0 - p0.0) + b * (p2.0 - p1.0) + c * (p3.0 - p2.0),
+                a * (p1.1 - p0.1) + b * (p2.1 - p1.1) + c * (p3.1 - p2.1),
+            )
+        };
+
+        // 单段二次曲线的最大误差约为 √3/36·|p3 - 3p2 + 3p1 - p0|，拆成n段后按n³缩小
+        let third = (p3.0 - 3.0 * p2.0 + 3.0 * p1.0 - p0.0, p3.1 - 3.0 * p2.1 + 3.0 * p1.1 - p0.1);
+        let error = 3f32.sqrt() / 36.0 * third.0.hypot(third.1);
+        let splits = ((error / CUBIC_TOLERANCE).cbrt().ceil() as usize).clamp(1, MAX_CUBIC_SPLITS);
+
+        let mut start = p0;
+        for i in 0..splits {
+            let (t0, t1) = (i as f32 / splits as f32, (i + 1) as f32 / splits as f32);
+            let end = if i + 1 == splits { p3 } else { cubic(t1) };
+            let (d0, d1) = (derivative(t0), derivative(t1));
+            let h = (t1 - t0) / 3.0;
+            let (c1, c2) = ((start.0 + h * d0.0, start.1 + h * d0.1), (end.0 - h * d1.0, end.1 - h * d1.1));
+            let control = (
+                (3.0 * (c1.0 + c2.0) - start.0 - end.0) / 4.0,
+                (3.0 * (c1.1 + c2.1) - start.1 - end.1) / 4.0,
+            );
+            if let Some(contour) = self.contours.last_mut() {
+                contour.push((control.0, control.1, false));
+                contour.push((end.0, end.1, true));
+            }
+            start = end;
+        }
+    }
+
+    fn close(&mut self) {
+        // 闭合轮廓时回到起点的点是多余的
+        if let Some(contour) = self.contours.last_mut() {
+            if contour.len() > 1 && contour.first() == contour.last() {
+                contour.pop();
+            }
+        }
+    }
+}
+
+fn round_coordinate(value: f32) -> i16 {
+    value.round().clamp(-MAX_COORDINATE, MAX_COORDINATE) as i16
+}
+
+/// 编码不含指令的glyf简单字形
+fn encode_simple_glyph(contours: &[Vec<(i16, i16, bool)>], bbox: [i16; 4]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend((contours.len() as i16).to_be_bytes());
+    for value in bbox {
+        data.extend(value.to_be_bytes());
+    }
+    let mut end_point = 0usize;
+    for contour in contours {
+        end_point += contour.len();
+        data.extend((end_point as u16 - 1).to_be_bytes());
+    }
+    data.extend(0u16.to_be_bytes());
+
+    let (mut flags, mut xs, mut ys) = (Vec::new(), Vec::new(), Vec::new());
+    let mut previous = (0i16, 0i16);
+    for &(x, y, on_curve) in contours.iter().flatten() {
+        let mut flag = if on_curve { ON_CURVE_POINT } else { 0 };
+        flag |= encode_delta(x - previous.0, X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE, &mut xs);
+        flag |= encode_delta(y - previous.1, Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE, &mut ys);
+        flags.push(flag);
+        previous = (x, y);
+    }
+    data.extend(flags);
+    data.extend(xs);
+    data.extend(ys);
+    data
+}
+
+/// 写入一个坐标差值，返回对应的点标志
+fn encode_delta(delta: i16, short_flag: u8, same_or_positive_flag: u8, out: &mut Vec<u8>) -> u8 {
+    match delta {
+        0 => same_or_positive_flag,
+        -255..=255 => {
+            out.push(delta.unsigned_abs() as u8);
+            if delta > 0 {
+                short_flag | same_or_positive_flag
+            } else {
+                short_flag
+            }
+        }
+        _ => {
+            out.extend(delta.to_be_bytes());
+            0
+        }
+    }
+}
+
+/// 按loca表拆分glyf表中的字形数据
+fn read_glyf(glyf: &[u8], loca: &[u8], num_glyphs: usize, long_offsets: bool) -> Result<Vec<Vec<u8>>> {
+    let offset = |index: usize| -> Option<usize> {
+        if long_offsets {
+            usize::try_from(u32::from_be_bytes(loca.get(index * 4..index * 4 + 4)?.try_into().ok()?)).ok()
+        } else {
+            Some(usize::from(u16::from_be_bytes(loca.get(index * 2..index * 2 + 2)?.try_into().ok()?)) * 2)
+        }
+    };
+    (0..num_glyphs)
+        .map(|index| {
+            let (start, end) = (offset(index), offset(index + 1));
+            start
+                .zip(end)
+                .and_then(|(start, end)| glyf.get(start..end))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| anyhow!("基础字体的loca表无效"))
+        })
+        .collect()
+}
+
+/// 读取所有字形的前进宽度和左侧间距
+fn read_hmtx(hmtx: &[u8], long_metrics: usize, num_glyphs: usize) -> Result<Vec<(u16, i16)>> {
+    if long_metrics == 0 || hmtx.len() < long_metrics * 4 {
+        return Err(anyhow!("基础字体的hmtx表无效"));
+    }
+    let mut metrics = Vec::with_capacity(num_glyphs);
+    let mut advance = 0;
+    for index in 0..num_glyphs {
+        let lsb = if index < long_metrics {
+            advance = read_u16(hmtx, index * 4);
+            read_u16(hmtx, index * 4 + 2)
+        } else {
+            read_u16(hmtx, long_metrics * 4 + (index - long_metrics) * 2)
+        };
+        metrics.push((advance, lsb as i16));
+    }
+    Ok(metrics)
+}
+
+/// 基础字体cmap中所有Unicode码点到字形的映射
+fn unicode_mappings(base: &[u8]) -> Result<BTreeMap<u32, u16>> {
+    let face = Face::parse(base, 0).map_err(|e| anyhow!("无法解析基础字体: {}", e))?;
+    let mut mappings = BTreeMap::new();
+    let Some(cmap) = face.tables().cmap else {
+        return Ok(mappings);
+    };
+    for subtable in cmap.subtables.into_iter().filter(|subtable| subtable.is_unicode()) {
+        subtable.codepoints(|codepoint| {
+            if let Some(glyph_id) = subtable.glyph_index(codepoint) {
+                mappings.entry(codepoint).or_insert(glyph_id.0);
+            }
+        });
+    }
+    Ok(mappings)
+}
+
+/// 生成glyf表和长格式的loca表
+fn build_glyf(glyphs: &[Vec<u8>]) -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = Vec::new();
+    let mut loca = Vec::with_capacity((glyphs.len() + 1) * 4);
+    for glyph in glyphs {
+        loca.extend((glyf.len() as u32).to_be_bytes());
+        glyf.extend(glyph);
+        glyf.resize(glyf.len().next_multiple_of(4), 0);
+    }
+    loca.extend((glyf.len() as u32).to_be_bytes());
+    (glyf, loca)
+}
+
+fn build_head(head: &[u8], appended: &[AppendedGlyph]) -> Vec<u8> {
+    let mut head = head.to_vec();
+    head[8..12].fill(0);
+    // 使用长格式的loca
+    head[50..52].copy_from_slice(&1i16.to_be_bytes());
+    let mut bbox = [36, 38, 40, 42].map(|offset| read_u16(&head, offset) as i16);
+    for glyph_bbox in appended.iter().filter_map(|glyph| glyph.bbox) {
+        bbox = [
+            bbox[0].min(glyph_bbox[0]),
+            bbox[1].min(glyph_bbox[1]),
+            bbox[2].max(glyph_bbox[2]),
+            bbox[3].max(glyph_bbox[3]),
+        ];
+    }
+    for (offset, value) in [36, 38, 40, 42].into_iter().zip(bbox) {
+        head[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+    head
+}
+
+fn build_maxp(maxp: &[u8], num_glyphs: usize, appended: &[AppendedGlyph]) -> Vec<u8> {
+    let mut maxp = maxp.to_vec();
+    maxp[4..6].copy_from_slice(&(num_glyphs as u16).to_be_bytes());
+    let max_points = appended.iter().map(|glyph| glyph.points).max().unwrap_or(0).max(read_u16(&maxp, 6));
+    let max_contours = appended.iter().map(|glyph| glyph.contours).max().unwrap_or(0).max(read_u16(&maxp, 8));
+    maxp[6..8].copy_from_slice(&max_points.to_be_bytes());
+    maxp[8..10].copy_from_slice(&max_contours.to_be_bytes());
+    maxp
+}
+
+fn build_hhea(hhea: &[u8], metrics: &[(u16, i16)], appended: &[AppendedGlyph]) -> Vec<u8> {
+    let mut hhea = hhea.to_vec();
+    let mut advance_max = read_u16(&hhea, 10);
+    let mut min_lsb = read_u16(&hhea, 12) as i16;
+    let mut min_rsb = read_u16(&hhea, 14) as i16;
+    let mut max_extent = read_u16(&hhea, 16) as i16;
+    for glyph in appended {
+        advance_max = advance_max.max(glyph.advance);
+        if let Some([x_min, _, x_max, _]) = glyph.bbox {
+            min_lsb = min_lsb.min(x_min);
+            min_rsb = min_rsb.min((i32::from(glyph.advance) - i32::from(x_max)).clamp(i16::MIN.into(), i16::MAX.into()) as i16);
+            max_extent = max_extent.max(x_max);
+        }
+    }
+    for (offset, value) in [(10, advance_max as i16), (12, min_lsb), (14, min_rsb), (16, max_extent)] {
+        hhea[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+    // hmtx中每个字形都写入完整的度量
+    hhea[34..36].copy_from_slice(&(metrics.len() as u16).to_be_bytes());
+    hhea
+}
+
+/// 生成cmap表：(3,1) 格式4子表覆盖基本多文种平面，(3,10) 格式12子表覆盖全部码点
+fn build_cmap(mappings: &BTreeMap<u32, u16>) -> Vec<u8> {
+    // 码点和字形编号都连续的区间：起始码点、结束码点、起始字形
+    let mut groups: Vec<(u32, u32, u16)> = Vec::new();
+    for (&codepoint, &glyph_id) in mappings {
+        match groups.last_mut() {
+            Some((start, end, start_glyph))
+                if *end + 1 == codepoint
+                    && u32::from(*start_glyph) + (codepoint - *start) == u32::from(glyph_id)
+                    && (codepoint <= 0xFFFF) == (*start <= 0xFFFF) =>
+            {
+                *end = codepoint;
+            }
+            _ => groups.push((codepoint, codepoint, glyph_id)),
+        }
+    }
+
+    let mut subtables = Vec::new();
+    // 0xFFFF保留给格式4的结束段
+    let bmp_groups: Vec<(u32, u32, u16)> = groups
+        .iter()
+        .filter(|group| group.0 < 0xFFFF)
+        .map(|&(start, end, glyph)| (start, end.min(0xFFFE), glyph))
+        .collect();
+    if let Some(format4) = build_cmap_format4(&bmp_groups) {
+        subtables.push((1u16, format4));
+    }
+    let mut format12 = Vec::with_capacity(16 + groups.len() * 12);
+    format12.extend(12u16.to_be_bytes());
+    format12.extend(0u16.to_be_bytes());
+    format12.extend(((16 + groups.len() * 12) as u32).to_be_bytes());
+    format12.extend(0u32.to_be_bytes());
+    format12.extend((groups.len() as u32).to_be_bytes());
+    for &(start, end, glyph) in &groups {
+        format12.extend(start.to_be_bytes());
+        format12.extend(end.to_be_bytes());
+        format12.extend(u32::from(glyph).to_be_bytes());
+    }
+    subtables.push((10u16, format12));
+
+    let mut cmap = Vec::new();
+    cmap.extend(0u16.to_be_bytes());
+    cmap.extend((subtables.len() as u16).to_be_bytes());
+    let mut offset = 4 + subtables.len() * 8;
+    for (encoding_id, subtable) in &subtables {
+        cmap.extend(3u16.to_be_bytes());
+        cmap.extend(encoding_id.to_be_bytes());
+        cmap.extend((offset as u32).to_be_bytes());
+        offset += subtable.len();
+    }
+    for (_, subtable) in subtables {
+        cmap.extend(subtable);
+    }
+    cmap
+}
+
+/// 生成格式4子表，长度超出u16时返回 `None`，此时只保留格式12子表
+fn build_cmap_format4(groups: &[(u32, u32, u16)]) -> Option<Vec<u8>> {
+    let segments: Vec<(u16, u16, u16)> = groups
+        .iter()
+        .map(|&(start, end, glyph)| (start as u16, end as u16, glyph.wrapping_sub(start as u16)))
+        .chain(std::iter::once((0xFFFF, 0xFFFF, 1)))
+        .collect();
+    let seg_count = segments.len();
+    let length = u16::try_from(16 + seg_count * 8).ok()?;
+    let entry_selector = seg_count.ilog2() as u16;
+    let search_range = 2u16 << entry_selector;
+
+    let mut format4 = Vec::with_capacity(usize::from(length));
+    for value in [4, length, 0, seg_count as u16 * 2, search_range, entry_selector, seg_count as u16 * 2 - search_range] {
+        format4.extend(value.to_be_bytes());
+    }
+    format4.extend(segments.iter().flat_map(|segment| segment.1.to_be_bytes()));
+    format4.extend(0u16.to_be_bytes());
+    format4.extend(segments.iter().flat_map(|segment| segment.0.to_be_bytes()));
+    format4.extend(segments.iter().flat_map(|segment| segment.2.to_be_bytes()));
+    format4.extend(segments.iter().flat_map(|_| 0u16.to_be_bytes()));
+    Some(format4)
+}
+
+/// 按标签顺序写出SFNT表，并更新 `head.checkSumAdjustment`
+fn build_sfnt(sfnt_version: u32, tables: &BTreeMap<[u8; 4], Vec<u8>>) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = num_tables.max(1).ilog2() as u16;
+    let search_range = 16u16 << entry_selector;
+
+    let mut font = Vec::new();
+    font.extend(sfnt_version.to_be_bytes());
+    for value in [num_tables, search_range, entry_selector, num_tables * 16 - search_range] {
+        font.extend(value.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        font.extend(tag);
+        font.extend(checksum(data).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for data in tables.values() {
+        font.extend(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = CHECKSUM_MAGIC.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+/// SFNT表的校验和：按大端u32累加，末尾不足4字节补零
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// 读取大端u16，越界时返回0
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2).map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
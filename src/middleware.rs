@@ -0,0 +1,171 @@
+use crate::{
+    error::{AppError, ErrorLanguage, ERROR_LANGUAGE},
+    utils::dated_log_path,
+    AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::{fmt, io::Read, path::Path, sync::Arc, time::{Instant, SystemTime}};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// 当前请求的ID，保存在请求扩展中供日志使用
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// 为每个请求分配 `X-Request-Id`，调用方已提供时原样返回
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let request_id = RequestId(
+        request
+            .headers()
+            .get(&X_REQUEST_ID)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+    );
+
+    log::info!("[{}] {} {}", request_id, request.method(), request.uri());
+    request.extensions_mut().insert(request_id.clone());
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+
+    log::info!("[{}] 响应状态 {}", request_id, response.status());
+    response
+}
+
+/// 按 `Accept-Language` 选择本次请求错误信息的语言
+pub async fn error_language(request: Request, next: Next) -> Response {
+    let language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(ErrorLanguage::from_accept_language)
+        .unwrap_or_default();
+    ERROR_LANGUAGE.scope(language, next.run(request)).await
+}
+
+/// 校验管理接口的 `Authorization: Bearer <token>` 请求头
+pub async fn require_admin(
+    State(service): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(expected) = service.config().admin_token.as_deref() else {
+        return Err(AppError::Unauthorized);
+    };
+    
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    
+    if provided != Some(expected) {
+        return Err(AppError::Unauthorized);
+    }
+    
+    Ok(next.run(request).await)
+}
+
+/// 解压 `Content-Encoding: gzip` 或 `deflate` 的请求体，解压后超过 `limit` 字节时返回413
+///
+/// 解压后的请求体以实际长度重新设置 `Content-Length`，内层的请求体大小限制按解压后的大小检查
+pub async fn decompress_request(
+    State(limit): State<usize>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let encoding = request
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
+    let Some(encoding) = encoding.filter(|e| e == "gzip" || e == "deflate") else {
+        return Ok(next.run(request).await);
+    };
+    
+    let (mut parts, body) = request.into_parts();
+    let compressed = axum::body::to_bytes(body, limit)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge(limit))?;
+    let decompressed = tokio::task::spawn_blocking(move || decompress_body(&encoding, &compressed, limit))
+        .await
+        .map_err(|e| AppError::InternalError(e.into()))??;
+    
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(decompressed.len()));
+    Ok(next.run(Request::from_parts(parts, Body::from(decompressed))).await)
+}
+
+/// 按编码解压，最多读取 `limit + 1` 字节以免压缩炸弹占满内存
+fn decompress_body(encoding: &str, data: &[u8], limit: usize) -> Result<Vec<u8>, AppError> {
+    let decoder: Box<dyn Read + '_> = if encoding == "gzip" {
+        Box::new(GzDecoder::new(data))
+    } else {
+        Box::new(ZlibDecoder::new(data))
+    };
+    
+    let mut decompressed = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| AppError::ConfigError(format!("请求体解压失败: {}", e)))?;
+    if decompressed.len() > limit {
+        return Err(AppError::PayloadTooLarge(limit));
+    }
+    Ok(decompressed)
+}
+
+/// 请求完成后向审计日志追加一行JSON，按UTC日期写入不同文件，每天自动轮换
+///
+/// 耗时统计到响应头返回为止，不包含响应体的传输时间
+pub async fn request_log(State(log_path): State<Arc<Path>>, request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().map(str::to_string);
+    let response = next.run(request).await;
+    
+    let timestamp = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    let entry = serde_json::json!({
+        "timestamp": timestamp,
+        "method": method,
+        "path": path,
+        "query": query,
+        "status": response.status().as_u16(),
+        "latency_ms": started.elapsed().as_millis() as u64,
+    });
+    let file_path = dated_log_path(&log_path, &timestamp[..10]);
+    if let Err(e) = append_line(&file_path, &format!("{}\n", entry)).await {
+        log::warn!("写入请求日志失败 {}: {}", file_path.display(), e);
+    }
+    response
+}
+
+/// 以追加模式写入一行，文件不存在时创建
+async fn append_line(path: &Path, line: &str) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    file.flush().await
+}
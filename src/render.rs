@@ -0,0 +1,203 @@
+use anyhow::Result;
+use flate2::{write::ZlibEncoder, Compression};
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// 二次曲线拆分的线段数
+const QUAD_SEGMENTS: usize = 8;
+/// 三次曲线拆分的线段数
+const CUBIC_SEGMENTS: usize = 12;
+/// 每个像素行的纵向采样次数
+const SAMPLES_PER_ROW: usize = 4;
+
+/// 把字形轮廓从字体单位换算到像素坐标（y轴向下），同时记录SVG路径和折线化后的轮廓
+pub struct GlyphOutline {
+    scale: f32,
+    offset_x: f32,
+    baseline: f32,
+    path: String,
+    contours: Vec<Vec<(f32, f32)>>,
+    current: (f32, f32),
+}
+
+impl GlyphOutline {
+    /// `scale` 为每字体单位对应的像素数，`offset_x` 和 `baseline` 为原点在图像中的位置
+    pub fn new(scale: f32, offset_x: f32, baseline: f32) -> Self {
+        Self {
+            scale,
+            offset_x,
+            baseline,
+            path: String::new(),
+            contours: Vec::new(),
+            current: (0.0, 0.0),
+        }
+    }
+    
+    fn map(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.offset_x + x * self.scale, self.baseline - y * self.scale)
+    }
+    
+    fn push_point(&mut self, point: (f32, f32)) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push(point);
+        }
+        self.current = point;
+    }
+    
+    /// SVG路径数据
+    pub fn svg_path(&self) -> &str {
+        self.path.trim_end()
+    }
+    
+    /// 以黑色填充字形，生成 `size`×`size` 的透明背景SVG
+    pub fn to_svg(&self, size: u32) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {0} {0}"><path d="{1}"/></svg>"#,
+            size,
+            self.svg_path()
+        )
+    }
+    
+    /// 按非零环绕规则光栅化，返回每个像素的覆盖率（0-255），逐行排列
+    pub fn rasterize(&self, size: u32) -> Vec<u8> {
+        let size = size as usize;
+        let mut coverage = vec![0f32; size * size];
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        
+        for row in 0..size {
+            let line = &mut coverage[row * size..(row + 1) * size];
+            for sample in 0..SAMPLES_PER_ROW {
+                let y = row as f32 + (sample as f32 + 0.5) / SAMPLES_PER_ROW as f32;
+                crossings.clear();
+                for contour in &self.contours {
+                    for (i, &(x0, y0)) in contour.iter().enumerate() {
+                        let (x1, y1) = contour[(i + 1) % contour.len()];
+                        if (y0 <= y) == (y1 <= y) {
+                            continue;
+                        }
+                        let x = x0 + (y - y0) / (y1 - y0) * (x1 - x0);
+                        crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    if winding != 0 {
+                        fill_span(line, pair[0].0, pair[1].0, 1.0 / SAMPLES_PER_ROW as f32);
+                    }
+                }
+            }
+        }
+        
+        coverage
+            .into_iter()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+/// 在一行像素上累加 `[start, end)` 区间的横向覆盖率
+fn fill_span(line: &mut [f32], start: f32, end: f32, weight: f32) {
+    let start = start.max(0.0);
+    let end = end.min(line.len() as f32);
+    if start >= end {
+        return;
+    }
+    
+    let first = start as usize;
+    let last = (end.ceil() as usize).min(line.len());
+    for (x, pixel) in line.iter_mut().enumerate().take(last).skip(first) {
+        let left = start.max(x as f32);
+        let right = end.min(x as f32 + 1.0);
+        *pixel += (right - left).max(0.0) * weight;
+    }
+}
+
+impl ttf_parser::OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.map(x, y);
+        let _ = write!(self.path, "M{:.2} {:.2} ", point.0, point.1);
+        self.contours.push(Vec::new());
+        self.push_point(point);
+    }
+    
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.map(x, y);
+        let _ = write!(self.path, "L{:.2} {:.2} ", point.0, point.1);
+        self.push_point(point);
+    }
+    
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (start, control, end) = (self.current, self.map(x1, y1), self.map(x, y));
+        let _ = write!(self.path, "Q{:.2} {:.2} {:.2} {:.2} ", control.0, control.1, end.0, end.1);
+        for i in 1..=QUAD_SEGMENTS {
+            let t = i as f32 / QUAD_SEGMENTS as f32;
+            let u = 1.0 - t;
+            self.push_point((
+                u * u * start.0 + 2.0 * u * t * control.0 + t * t * end.0,
+                u * u * start.1 + 2.0 * u * t * control.1 + t * t * end.1,
+            ));
+        }
+    }
+    
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (start, c1, c2, end) = (self.current, self.map(x1, y1), self.map(x2, y2), self.map(x, y));
+        let _ = write!(
+            self.path,
+            "C{:.2} {:.2} {:.2} {:.2} {:.2} {:.2} ",
+            c1.0, c1.1, c2.0, c2.1, end.0, end.1
+        );
+        for i in 1..=CUBIC_SEGMENTS {
+            let t = i as f32 / CUBIC_SEGMENTS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.push_point((
+                a * start.0 + b * c1.0 + c * c2.0 + d * end.0,
+                a * start.1 + b * c1.1 + c * c2.1 + d * end.1,
+            ));
+        }
+    }
+    
+    fn close(&mut self) {
+        self.path.push_str("Z ");
+    }
+}
+
+/// 把覆盖率编码为黑色、透明背景的灰度+透明度PNG
+pub fn encode_png(coverage: &[u8], size: u32) -> Result<Vec<u8>> {
+    let mut raw = Vec::with_capacity(coverage.len() * 2 + size as usize);
+    for row in coverage.chunks(size as usize) {
+        // 每行以过滤类型0（不过滤）开头
+        raw.push(0);
+        for &alpha in row {
+            raw.extend_from_slice(&[0, alpha]);
+        }
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw)?;
+    let compressed = encoder.finish()?;
+    
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&size.to_be_bytes());
+    ihdr.extend_from_slice(&size.to_be_bytes());
+    // 位深8，颜色类型4（灰度+透明度），默认压缩、过滤和不隔行
+    ihdr.extend_from_slice(&[8, 4, 0, 0, 0]);
+    
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &compressed);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
@@ -1,48 +1,293 @@
 use crate::{
-    config::{AppConfig, FontConfig},
+    batcher::{store_cache_file, write_cache_file, WriteBatcher},
+    config::{is_valid_font_id, resolve_extends, AppConfig, FallbackEntry, FontConfig, FontFile, LocalizedText, WarmupStrategy},
     error::AppError,
-    font::FontProcessor,
-    utils::{generate_cache_filename, cleanup_expired_cache},
+    font::{FontProcessor, VariableAxis, Woff2Header},
+    merge::append_glyphs,
+    subsetter::BackgroundSubsetter,
+    utils::{
+        content_digest, format_unicode_ranges, generate_cache_filename, generate_file_hash, generate_ttf_cache_filename, generate_unhinted_cache_filename, cleanup_expired_cache, is_font_file, parse_cache_filename,
+        parse_charset_file, sha256_hex, unicode_block_range, EMOJI_BLOCKS, HAN_IDEOGRAPH_BLOCKS,
+    },
 };
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
-use tokio::sync::RwLock;
+use tokio::{io::AsyncReadExt, sync::RwLock, task::JoinHandle};
 use walkdir::WalkDir;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FontInfo {
     pub id: String,
     pub version: String,
     pub font_family: String,
     pub license: String,
-    pub fallback: Vec<String>,
+    pub fallback: Vec<FallbackEntry>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<crate::config::LocalizedText>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<crate::config::LocalizedText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<crate::config::LocalizedText>,
+    /// 按请求语言选择的显示名称，仅在 `?localized=true` 时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    /// 第一个字体文件的字形数量
+    pub glyph_count: u32,
+    /// 第一个字体文件是否包含彩色字形
+    pub color_emoji: bool,
+    /// 第一个字体文件的可变轴，非可变字体不返回
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub axes: Vec<VariableAxis>,
+    /// 所有字体文件的SHA-256是否都已校验通过
+    pub checksum_verified: bool,
+    /// 按字体族合并时同一字体族的所有字体配置，仅在 `?deduplicate=true` 时返回
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<FontInfo>,
+}
+
+impl FontInfo {
+    /// 按 `font_family` 合并字体，每个字体族保留第一个字体作为代表，多于一个字体时在 `variants` 中列出全部字体
+    pub fn group_by_family(fonts: Vec<FontInfo>) -> Vec<FontInfo> {
+        let mut groups: Vec<Vec<FontInfo>> = Vec::new();
+        for font in fonts {
+            match groups.iter_mut().find(|group| group[0].font_family == font.font_family) {
+                Some(group) => group.push(font),
+                None => groups.push(vec![font]),
+            }
+        }
+        
+        groups
+            .into_iter()
+            .map(|mut group| {
+                if group.len() == 1 {
+                    return group.remove(0);
+                }
+                let mut representative = group[0].clone();
+                representative.variants = group;
+                representative
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlyphPage {
+    pub codepoints: Vec<u32>,
+    pub page: usize,
+    pub total: usize,
+}
+
+/// 分页的字符映射表，序列化为以 `U+XXXX` 为键、字形ID为值的对象
+#[derive(Debug, Clone, Serialize)]
+pub struct CmapPage {
+    #[serde(serialize_with = "serialize_cmap_entries")]
+    pub entries: Vec<(u32, u16)>,
+    pub page: usize,
+    pub total: usize,
+}
+
+/// 按码点顺序输出映射，避免对象键按字符串排序
+fn serialize_cmap_entries<S: serde::Serializer>(entries: &[(u32, u16)], serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut map = serializer.serialize_map(Some(entries.len()))?;
+    for (codepoint, glyph_id) in entries {
+        map.serialize_entry(&format!("U+{:04X}", codepoint), glyph_id)?;
+    }
+    map.end()
+}
+
+impl CmapPage {
+    /// 从完整的映射表中取出一页，页码从1开始
+    pub fn paginate(entries: Vec<(u32, u16)>, page: usize, per_page: usize) -> Self {
+        let total = entries.len();
+        let entries = entries
+            .into_iter()
+            .skip(page.saturating_sub(1).saturating_mul(per_page))
+            .take(per_page)
+            .collect();
+        CmapPage { entries, page, total }
+    }
+}
+
+/// 覆盖率热力图中的一行，对应一个CJK统一表意文字区块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageRow {
+    pub block: String,
+    /// 区块起始码点，`U+XXXX` 格式
+    pub start: String,
+    /// 每个单元格的覆盖百分比，最后一格可能不足 `HEATMAP_CELL_SIZE` 个码点
+    pub cells: Vec<f32>,
+}
+
+/// 字体对emoji区块的覆盖情况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmojiCoverage {
+    pub covered: usize,
+    /// emoji区块的码点总数，包括未分配的码点
+    pub total: usize,
+    pub codepoints: Vec<u32>,
+}
+
+/// 热力图每个单元格包含的码点数量
+pub const HEATMAP_CELL_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchFailure {
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FontBatchSummary {
+    pub loaded: usize,
+    pub fonts: Vec<String>,
+    pub failed: Vec<BatchFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheManifestEntry {
+    pub font_id: String,
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheManifest {
+    pub generated_at: String,
+    pub entries: Vec<CacheManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FontVerifyFailure {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FontVerifyReport {
+    pub ok: Vec<String>,
+    pub failed: Vec<FontVerifyFailure>,
+}
+
+/// 缓存去重的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheCompactReport {
+    /// 扫描的缓存文件数量
+    pub files: usize,
+    /// 替换为硬链接的重复文件数量
+    pub linked: usize,
+    /// 节省的磁盘空间（字节）
+    pub saved_bytes: u64,
+}
+
+/// 单个字体文件的查询结果
+#[derive(Debug, Clone, Serialize)]
+pub struct FontFileInfo<T> {
+    pub font_family: String,
+    pub data: T,
+}
+
+/// 缓存的字体文件及其修改时间
+pub struct CachedFont {
+    pub data: Vec<u8>,
+    pub modified: Option<SystemTime>,
+    /// 文件内容的BLAKE3哈希
+    pub hash: String,
+    /// `Content-Digest` 响应头的值
+    pub content_digest: String,
+    /// 子集中的字形数量，无法解析时为 `None`
+    pub glyph_count: Option<u16>,
+}
+
+/// 缓存文件内容的摘要，文件的修改时间和大小不变时复用
+#[derive(Clone)]
+struct SubsetMeta {
+    modified: Option<SystemTime>,
+    len: usize,
+    hash: String,
+    content_digest: String,
+    glyph_count: Option<u16>,
+}
+
+impl SubsetMeta {
+    fn compute(data: &[u8], modified: Option<SystemTime>) -> Self {
+        Self {
+            modified,
+            len: data.len(),
+            hash: generate_file_hash(data),
+            content_digest: content_digest(data),
+            glyph_count: FontProcessor::subset_glyph_count(data),
+        }
+    }
 }
 
+/// 单独加载的字体目录：字体ID、字体配置和字体处理器
+type LoadedFontDir = (String, HashMap<String, FontConfig>, HashMap<String, Arc<FontProcessor>>);
+
+/// 字体目录中存放替换字形的子目录
+const OVERRIDES_DIR: &str = "overrides";
+
+/// 缓存文件扩展属性的名称
+#[cfg(target_os = "linux")]
+const XATTR_FONT_ID: &str = "user.webfont.font_id";
+#[cfg(target_os = "linux")]
+const XATTR_CODEPOINTS: &str = "user.webfont.codepoints";
+
+/// 码点请求次数的持久化文件名
+const REQUEST_COUNTS_FILENAME: &str = "codepoint_frequency.json";
+
 pub struct FontService {
     config: AppConfig,
     fonts: Arc<RwLock<HashMap<String, FontConfig>>>,
     processors: Arc<RwLock<HashMap<String, Arc<FontProcessor>>>>,
+    cleanup_task: Option<JoinHandle<()>>,
+    subsetter: BackgroundSubsetter,
+    /// 每个码点被请求的次数，用于 `TopN` 预热
+    request_counts: Mutex<HashMap<u32, u64>>,
+    /// 自启动以来每个字体被请求的次数
+    font_request_counts: Mutex<HashMap<String, u64>>,
+    /// 正在生成的缓存文件，同一文件的并发请求只生成一次
+    generating: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// 新生成的缓存文件先放入缓冲区再批量写入
+    write_batcher: WriteBatcher,
+    /// 按 `(字体ID, WOFF2内容的BLAKE3哈希)` 记录的缓存文件，用于固定链接访问
+    subset_hashes: Arc<Mutex<HashMap<(String, String), PathBuf>>>,
+    /// 按路径记录的缓存文件摘要，缓存命中时不必重新计算哈希和字形数量
+    subset_meta: Mutex<HashMap<PathBuf, SubsetMeta>>,
 }
 
 impl FontService {
     pub async fn new(config: AppConfig) -> Result<Self> {
-        let service = Self {
+        let mut service = Self {
             config,
             fonts: Arc::new(RwLock::new(HashMap::new())),
             processors: Arc::new(RwLock::new(HashMap::new())),
+            cleanup_task: None,
+            subsetter: BackgroundSubsetter::new(),
+            request_counts: Mutex::new(HashMap::new()),
+            font_request_counts: Mutex::new(HashMap::new()),
+            generating: Mutex::new(HashMap::new()),
+            write_batcher: WriteBatcher::new(),
+            subset_hashes: Arc::new(Mutex::new(HashMap::new())),
+            subset_meta: Mutex::new(HashMap::new()),
         };
         
         service.load_fonts().await?;
-        service.start_cleanup_task();
+        service.load_request_counts();
+        service.index_cached_subsets();
+        service.cleanup_task = Some(service.start_cleanup_task());
         
         Ok(service)
     }
@@ -50,201 +295,1626 @@ impl FontService {
     /// 加载所有字体配置
     async fn load_fonts(&self) -> Result<()> {
         let fonts_dir = self.config.data_dir.join("fonts");
+        let mut loaded_fonts = HashMap::new();
+        let mut loaded_processors = HashMap::new();
+        for font_dir in list_subdirs(&fonts_dir).await {
+            // 读取和解析字体文件是阻塞操作，放到阻塞线程池中执行
+            let dir = font_dir.clone();
+            let loaded = tokio::task::spawn_blocking(move || {
+                let mut fonts = HashMap::new();
+                let mut processors = HashMap::new();
+                Self::load_font_dir(&dir, &mut fonts, &mut processors)?;
+                Ok::<_, anyhow::Error>((fonts, processors))
+            })
+            .await?;
+            match loaded {
+                Ok((fonts, processors)) => {
+                    loaded_fonts.extend(fonts);
+                    loaded_processors.extend(processors);
+                }
+                Err(e) => log::error!("加载字体配置失败 {}: {}", font_dir.display(), e),
+            }
+        }
+        
         let mut fonts = self.fonts.write().await;
-        let mut processors = self.processors.write().await;
+        fonts.extend(loaded_fonts);
+        self.processors.write().await.extend(loaded_processors);
+        resolve_extends(&mut fonts)?;
         
-        for entry in WalkDir::new(&fonts_dir)
-            .min_depth(1)
-            .max_depth(1)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_dir())
-        {
-            let font_dir = entry.path();
-            
-            match FontConfig::load_from_dir(&font_dir.to_path_buf()) {
-                Ok(font_config) => {
-                    log::info!("加载字体配置: {}", font_config.id);
-                    
-                    // 为每个字体文件创建处理器
-                    for font_file in &font_config.files {
-                        let font_path = font_dir.join(&font_file.path);
-                        if font_path.exists() {
-                            match FontProcessor::new(&font_path) {
-                                Ok(processor) => {
-                                    let key = format!("{}:{}", font_config.id, font_file.font_family);
-                                    processors.insert(key, Arc::new(processor));
-                                    log::info!("加载字体处理器: {} - {}", font_config.id, font_file.font_family);
-                                }
-                                Err(e) => {
-                                    log::error!("加载字体处理器失败 {}: {}", font_path.display(), e);
-                                }
-                            }
-                        } else {
-                            log::error!("字体文件不存在: {}", font_path.display());
+        log::info!("共加载 {} 个字体配置", fonts.len());
+        Ok(())
+    }
+    
+    /// 加载单个字体目录，返回字体ID
+    fn load_font_dir(
+        font_dir: &Path,
+        fonts: &mut HashMap<String, FontConfig>,
+        processors: &mut HashMap<String, Arc<FontProcessor>>,
+    ) -> Result<String> {
+        let mut font_config = if font_dir.join("config.json").exists() {
+            FontConfig::load_from_dir(font_dir)?
+        } else {
+            FontConfig::detect_from_dir(font_dir)?
+        };
+        log::info!("加载字体配置: {}", font_config.id);
+        
+        // 为每个字体文件创建处理器
+        let mut loaded = Vec::new();
+        let mut checksum_verified = !font_config.files.is_empty();
+        for font_file in &font_config.files {
+            let font_path = font_dir.join(&font_file.path);
+            if font_path.exists() {
+                match FontProcessor::new(&font_path) {
+                    Ok(processor) => {
+                        for warning in processor.validate() {
+                            log::warn!("字体文件校验警告 {}: {}", font_path.display(), warning);
                         }
+                        if !processor.family_name_matches(&font_file.font_family) {
+                            log::warn!(
+                                "字体文件 {} 内部的字体族名与配置的 {} 不一致",
+                                font_path.display(),
+                                font_file.font_family
+                            );
+                        }
+                        checksum_verified &= Self::verify_checksum(font_file, &font_path, processor.font_data());
+                        let key = format!("{}:{}", font_config.id, font_file.font_family);
+                        loaded.push((key, processor));
+                    }
+                    Err(e) => {
+                        checksum_verified = false;
+                        log::error!("加载字体处理器失败 {}: {}", font_path.display(), e);
                     }
-                    
-                    fonts.insert(font_config.id.clone(), font_config);
                 }
-                Err(e) => {
-                    log::error!("加载字体配置失败 {}: {}", font_dir.display(), e);
+            } else {
+                checksum_verified = false;
+                log::error!("字体文件不存在: {}", font_path.display());
+            }
+        }
+        font_config.checksum_verified = checksum_verified;
+        
+        let glyph_count: usize = loaded
+            .iter()
+            .map(|(_, processor)| usize::from(processor.number_of_glyphs()))
+            .sum();
+        if glyph_count < font_config.min_codepoints_in_font {
+            return Err(anyhow::anyhow!(
+                "字体 {} 只包含 {} 个字形，少于要求的 {} 个",
+                font_config.id,
+                glyph_count,
+                font_config.min_codepoints_in_font
+            ));
+        }
+        
+        // 替换字体不计入字形数量，加载失败时只影响对应区块
+        for block in &font_config.overrides {
+            if unicode_block_range(block).is_none() {
+                log::error!("未知的Unicode区块 {}: {}", font_config.id, block);
+                continue;
+            }
+            let font_path = font_dir.join(OVERRIDES_DIR).join(format!("{}.ttf", block));
+            match FontProcessor::new(&font_path) {
+                Ok(processor) => loaded.push((override_key(&font_config.id, block), processor)),
+                Err(e) => log::error!("加载替换字体失败 {}: {}", font_path.display(), e),
+            }
+        }
+        
+        for (key, processor) in loaded {
+            log::info!("加载字体处理器: {}", key);
+            processors.insert(key, Arc::new(processor));
+        }
+        
+        let font_id = font_config.id.clone();
+        fonts.insert(font_id.clone(), font_config);
+        Ok(font_id)
+    }
+    
+    /// 检查提交的字体配置，字体文件按 `fonts/{id}/` 目录中的路径检查是否存在
+    pub fn validate_font_config(&self, font_config: &FontConfig) -> Vec<String> {
+        let mut errors = font_config.validate();
+        if is_valid_font_id(&font_config.id) {
+            let font_dir = self.config.data_dir.join("fonts").join(&font_config.id);
+            // 不在字体目录内的路径已由 `validate` 报告，不检查是否存在，避免泄露服务器上的文件
+            for font_file in font_config.files.iter().filter(|font_file| font_file.is_within_font_dir()) {
+                if !font_dir.join(&font_file.path).is_file() {
+                    errors.push(format!("字体文件不存在: {}", font_file.path));
                 }
             }
         }
+        errors
+    }
+    
+    /// 重新加载单个字体，读取和解析文件时不持有锁，只在替换条目时短暂获取写锁
+    pub async fn reload_font(&self, font_id: &str) -> Result<()> {
+        let font_dir = self
+            .fonts
+            .read()
+            .await
+            .get(font_id)
+            .map(|config| config.dir.clone())
+            .ok_or_else(|| anyhow::anyhow!("字体未找到: {}", font_id))?;
         
-        log::info!("共加载 {} 个字体配置", fonts.len());
+        let (mut loaded_fonts, loaded_processors) = tokio::task::spawn_blocking(move || {
+            let mut fonts = HashMap::new();
+            let mut processors = HashMap::new();
+            Self::load_font_dir(&font_dir, &mut fonts, &mut processors)?;
+            Ok::<_, anyhow::Error>((fonts, processors))
+        })
+        .await??;
+        let mut font_config = loaded_fonts
+            .remove(font_id)
+            .ok_or_else(|| anyhow::anyhow!("字体目录中的字体ID已变更: {}", font_id))?;
+        
+        // 在副本上解析继承，失败时不影响正在使用的配置
+        let mut configs = self.fonts.read().await.clone();
+        // 保留运行时写入但配置文件中没有的翻译
+        if let Some(current) = configs.get(font_id) {
+            font_config.name = merge_localized(&current.name, font_config.name);
+            font_config.title = merge_localized(&current.title, font_config.title);
+            font_config.description = merge_localized(&current.description, font_config.description);
+        }
+        configs.insert(font_id.to_string(), font_config);
+        resolve_extends(&mut configs)?;
+        let font_config = configs[font_id].clone();
+        
+        let mut fonts = self.fonts.write().await;
+        let mut processors = self.processors.write().await;
+        let prefix = format!("{}:", font_id);
+        processors.retain(|key, _| !key.starts_with(&prefix));
+        processors.extend(loaded_processors);
+        fonts.insert(font_id.to_string(), font_config);
+        
+        log::info!("重新加载字体: {}", font_id);
         Ok(())
     }
     
+    /// 校验字体文件的SHA-256，未声明校验值或不匹配时返回false
+    fn verify_checksum(font_file: &FontFile, font_path: &Path, font_data: &[u8]) -> bool {
+        let Some(expected) = &font_file.sha256 else {
+            return false;
+        };
+        
+        let actual = sha256_hex(font_data);
+        if actual.eq_ignore_ascii_case(expected) {
+            true
+        } else {
+            log::warn!(
+                "字体文件校验值不匹配 {}: 期望 {}，实际 {}",
+                font_path.display(),
+                expected,
+                actual
+            );
+            false
+        }
+    }
+    
+    /// 从ZIP压缩包批量安装字体，压缩包内每个顶层目录对应一个字体
+    ///
+    /// 解压和加载字体时不持有锁，只在登记加载成功的字体时短暂获取写锁。
+    /// 字体ID无效或与已加载的字体重复的目录会被删除并计入失败
+    pub async fn install_font_archive(&self, data: Vec<u8>) -> Result<FontBatchSummary, AppError> {
+        let fonts_dir = self.config.data_dir.join("fonts");
+        let max_bytes = self.config.max_archive_extract_bytes;
+        let (font_dirs, mut failed) =
+            tokio::task::spawn_blocking(move || extract_font_archive(&data, &fonts_dir, max_bytes))
+                .await
+                .map_err(|e| AppError::InternalError(e.into()))?
+                .map_err(|e| AppError::FontProcessingError(format!("解压字体压缩包失败: {}", e)))?;
+        
+        let known: HashSet<String> = self.fonts.read().await.keys().cloned().collect();
+        let results = tokio::task::spawn_blocking(move || {
+            let mut known = known;
+            font_dirs
+                .into_iter()
+                .map(|font_dir| {
+                    let result = Self::load_archive_font_dir(&font_dir, &known);
+                    if let Ok((font_id, _, _)) = &result {
+                        known.insert(font_id.clone());
+                    }
+                    (font_dir, result)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.into()))?;
+        
+        let mut loaded = Vec::new();
+        let mut rejected = Vec::new();
+        {
+            let mut fonts = self.fonts.write().await;
+            let mut processors = self.processors.write().await;
+            for (font_dir, result) in results {
+                // 加载期间其他请求可能已经登记了同一个ID
+                let result = result.and_then(|(font_id, loaded_fonts, loaded_processors)| {
+                    if fonts.contains_key(&font_id) {
+                        return Err(anyhow::anyhow!("字体ID已存在: {}", font_id));
+                    }
+                    fonts.extend(loaded_fonts);
+                    processors.extend(loaded_processors);
+                    Ok(font_id)
+                });
+                match result {
+                    Ok(font_id) => loaded.push(font_id),
+                    Err(e) => rejected.push((font_dir, e)),
+                }
+            }
+            
+            if let Err(e) = resolve_extends(&mut fonts) {
+                log::error!("解析字体继承失败: {}", e);
+            }
+        }
+        
+        for (font_dir, e) in rejected {
+            log::error!("安装字体失败 {}: {}", font_dir.display(), e);
+            if let Err(e) = tokio::fs::remove_dir_all(&font_dir).await {
+                log::warn!("删除字体目录失败 {}: {}", font_dir.display(), e);
+            }
+            failed.push(BatchFailure {
+                name: font_dir.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                error: e.to_string(),
+            });
+        }
+        
+        log::info!("批量安装字体完成: 成功 {} 个，失败 {} 个", loaded.len(), failed.len());
+        Ok(FontBatchSummary {
+            loaded: loaded.len(),
+            fonts: loaded,
+            failed,
+        })
+    }
+    
+    /// 加载压缩包中解压出的字体目录，字体ID无效或已在 `known` 中时返回错误
+    fn load_archive_font_dir(
+        font_dir: &Path,
+        known: &HashSet<String>,
+    ) -> Result<LoadedFontDir> {
+        let mut fonts = HashMap::new();
+        let mut processors = HashMap::new();
+        let font_id = Self::load_font_dir(font_dir, &mut fonts, &mut processors)?;
+        if !is_valid_font_id(&font_id) {
+            return Err(anyhow::anyhow!("无效的字体ID: {}", font_id));
+        }
+        if known.contains(&font_id) {
+            return Err(anyhow::anyhow!("字体ID已存在: {}", font_id));
+        }
+        Ok((font_id, fonts, processors))
+    }
+    
+    /// 获取服务配置
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+    
+    /// 获取后台子集化队列
+    pub fn subsetter(&self) -> &BackgroundSubsetter {
+        &self.subsetter
+    }
+    
     /// 获取所有字体信息
     pub async fn list_fonts(&self) -> Vec<FontInfo> {
         let fonts = self.fonts.read().await;
+        let processors = self.processors.read().await;
         fonts
             .values()
-            .map(|config| FontInfo {
-                id: config.id.clone(),
-                version: config.version.clone(),
-                font_family: config.font_family.clone(),
-                license: config.license.clone(),
-                fallback: config.fallback.clone(),
-                name: config.name.clone(),
-                title: config.title.clone(),
-            })
+            .map(|config| Self::font_info(config, &processors))
             .collect()
     }
     
-    /// 生成字体WOFF2文件
-    pub async fn generate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<Vec<u8>, AppError> {
-        if codepoints.is_empty() {
-            return Err(AppError::CharacterNotFound(0));
-        }
+    /// 列出包含指定码点的所有字体，按ID排序
+    pub async fn fonts_covering_codepoint(&self, codepoint: u32) -> Vec<FontInfo> {
+        let fonts = self.fonts.read().await;
+        let processors = self.processors.read().await;
         
-        // 如果指定了字体ID，直接使用该字体
-        if let Some(id) = font_id {
-            return self.generate_font_by_id(id, codepoints).await;
+        let mut font_infos: Vec<FontInfo> = fonts
+            .values()
+            .filter(|config| {
+                config.files.iter().any(|font_file| {
+                    let key = format!("{}:{}", config.id, font_file.font_family);
+                    processors
+                        .get(&key)
+                        .is_some_and(|processor| processor.contains_char(codepoint))
+                })
+            })
+            .map(|config| Self::font_info(config, &processors))
+            .collect();
+        font_infos.sort_by(|a, b| a.id.cmp(&b.id));
+        font_infos
+    }
+    
+    /// 计算每个字体自身文件对请求码点的覆盖率（百分比），按覆盖率升序排列，相同时按ID排序
+    ///
+    /// 与生成字体时的查找相反，不考虑回退字体，重复的码点只计算一次
+    pub async fn least_covered_for(&self, codepoints: &[u32]) -> Vec<(String, f64)> {
+        let requested: BTreeSet<u32> = codepoints.iter().copied().collect();
+        if requested.is_empty() {
+            return Vec::new();
         }
         
-        // 否则尝试所有字体，使用第一个包含字符的字体
         let fonts = self.fonts.read().await;
-        for font_config in fonts.values() {
-            if let Ok(woff2_data) = self.generate_font_by_id(&font_config.id, codepoints).await {
-                return Ok(woff2_data);
+        let processors = self.processors.read().await;
+        let mut coverage: Vec<(String, f64)> = fonts
+            .values()
+            .map(|config| {
+                let font_processors: Vec<&Arc<FontProcessor>> = config
+                    .files
+                    .iter()
+                    .filter_map(|font_file| processors.get(&format!("{}:{}", config.id, font_file.font_family)))
+                    .collect();
+                let covered = requested
+                    .iter()
+                    .filter(|&&cp| font_processors.iter().any(|processor| processor.contains_char(cp)))
+                    .count();
+                (config.id.clone(), covered as f64 * 100.0 / requested.len() as f64)
+            })
+            .collect();
+        coverage.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        coverage
+    }
+    
+    fn font_info(config: &FontConfig, processors: &HashMap<String, Arc<FontProcessor>>) -> FontInfo {
+        let mut glyph_count = 0;
+        let mut color_emoji = false;
+        let mut axes = Vec::new();
+        if let Some(font_file) = config.files.first() {
+            let key = format!("{}:{}", config.id, font_file.font_family);
+            if let Some(processor) = processors.get(&key) {
+                glyph_count = u32::from(processor.number_of_glyphs());
+                color_emoji = processor.has_color_glyphs();
+                axes = processor.variable_axes();
             }
         }
         
-        Err(AppError::CharacterNotFound(codepoints[0]))
+        FontInfo {
+            id: config.id.clone(),
+            version: config.version.clone(),
+            font_family: config.font_family.clone(),
+            license: config.license.clone(),
+            fallback: config.fallback.clone(),
+            name: config.name.clone(),
+            title: config.title.clone(),
+            description: config.description.clone(),
+            display_name: None,
+            source_url: config.source_url.clone(),
+            annotations: config.annotations.clone(),
+            glyph_count,
+            color_emoji,
+            axes,
+            checksum_verified: config.checksum_verified,
+            variants: Vec::new(),
+        }
+    }
+    
+    /// 获取字体文件对应的 `FontProcessor`，需要独占的HarfBuzz实例时可以克隆
+    pub async fn get_font_processor(&self, font_id: &str, family: &str) -> Option<Arc<FontProcessor>> {
+        let key = format!("{}:{}", font_id, family);
+        self.processors.read().await.get(&key).cloned()
     }
     
-    /// 根据字体ID生成WOFF2文件
-    async fn generate_font_by_id(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<u8>, AppError> {
+    /// 对字体的每个文件执行查询
+    pub async fn inspect_font_files<T>(
+        &self,
+        font_id: &str,
+        inspect: impl Fn(&FontProcessor) -> T,
+    ) -> Result<Vec<FontFileInfo<T>>, AppError> {
         let fonts = self.fonts.read().await;
         let font_config = fonts
             .get(font_id)
             .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
         
-        // 尝试每个字体文件，直到找到包含字符的文件
         let processors = self.processors.read().await;
+        let mut results = Vec::new();
         for font_file in &font_config.files {
             let key = format!("{}:{}", font_id, font_file.font_family);
             if let Some(processor) = processors.get(&key) {
-                let available_chars = processor.get_available_chars(codepoints);
-                if !available_chars.is_empty() {
-                    match processor.generate_woff2(&available_chars) {
-                        Ok(woff2_data) => return Ok(woff2_data),
-                        Err(e) => log::warn!("生成WOFF2失败 {}: {}", key, e),
-                    }
-                }
+                results.push(FontFileInfo {
+                    font_family: font_file.font_family.clone(),
+                    data: inspect(processor),
+                });
             }
         }
         
-        // 如果当前字体不包含字符，尝试fallback字体
-        for fallback_id in &font_config.fallback {
-            let fallback_result = Box::pin(self.generate_font_by_id(fallback_id, codepoints)).await;
-            if let Ok(woff2_data) = fallback_result {
-                return Ok(woff2_data);
-            }
+        Ok(results)
+    }
+    
+    /// 获取字体的上游项目地址
+    pub async fn font_source_url(&self, font_id: &str) -> Result<Option<String>, AppError> {
+        let fonts = self.fonts.read().await;
+        let font_config = fonts
+            .get(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+        Ok(font_config.source_url.clone())
+    }
+    
+    /// 获取字体的运维标签
+    pub async fn font_annotations(&self, font_id: &str) -> Result<HashMap<String, String>, AppError> {
+        let fonts = self.fonts.read().await;
+        let font_config = fonts
+            .get(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+        Ok(font_config.annotations.clone())
+    }
+    
+    /// 合并修改字体的运维标签并写回 `config.json`，值为 `None` 的键会被删除
+    pub async fn update_annotations(
+        &self,
+        font_id: &str,
+        patch: HashMap<String, Option<String>>,
+    ) -> Result<HashMap<String, String>, AppError> {
+        let mut fonts = self.fonts.write().await;
+        let font_config = fonts
+            .get_mut(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+        
+        let mut annotations = font_config.annotations.clone();
+        for (key, value) in patch {
+            match value {
+                Some(value) => annotations.insert(key, value),
+                None => annotations.remove(&key),
+            };
         }
         
-        Err(AppError::CharacterNotFound(codepoints[0]))
+        // 重新读取原始配置再写回，避免把 `extends` 继承来的字段写入文件
+        let mut stored = FontConfig::load_from_dir(&font_config.dir)?;
+        stored.annotations = annotations.clone();
+        stored.save_to_dir(&font_config.dir)?;
+        log::info!("更新字体标签: {}", font_id);
+        
+        font_config.annotations = annotations.clone();
+        Ok(annotations)
     }
     
-    /// 获取或生成缓存的字体文件
-    pub async fn get_cached_font(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<u8>, AppError> {
-        let cache_filename = generate_cache_filename(codepoints);
-        let cache_path = self.config.static_dir.join(font_id).join(&cache_filename);
+    /// 修改字体ID：重命名字体目录和缓存目录，更新 `config.json`，并在内存中替换，不重新加载字体文件
+    ///
+    /// 其他字体 `fallback` 中引用的旧ID不会自动修改
+    pub async fn rename_font(&self, old_id: &str, new_id: &str) -> Result<(), AppError> {
+        if !is_valid_font_id(new_id) {
+            return Err(AppError::ConfigError(format!("无效的字体ID: {}", new_id)));
+        }
         
-        // 检查缓存是否存在
-        if cache_path.exists() {
-            match tokio::fs::read(&cache_path).await {
-                Ok(data) => {
-                    log::debug!("使用缓存文件: {:?}", cache_path);
-                    return Ok(data);
-                }
-                Err(e) => log::warn!("读取缓存文件失败 {:?}: {}", cache_path, e),
-            }
+        let mut fonts = self.fonts.write().await;
+        let mut processors = self.processors.write().await;
+        if fonts.contains_key(new_id) {
+            return Err(AppError::FontExists(new_id.to_string()));
+        }
+        let old_dir = fonts
+            .get(old_id)
+            .map(|config| config.dir.clone())
+            .ok_or_else(|| AppError::FontNotFound(old_id.to_string()))?;
+        let new_dir = old_dir.with_file_name(new_id);
+        if new_dir.exists() {
+            return Err(AppError::FontExists(new_id.to_string()));
         }
         
-        // 生成新的字体文件
-        let woff2_data = self.generate_font(Some(font_id), codepoints).await?;
+        // 先改配置再移动目录，移动失败时恢复配置
+        let mut stored = FontConfig::load_from_dir(&old_dir)?;
+        stored.id = new_id.to_string();
+        stored.save_to_dir(&old_dir)?;
+        if let Err(e) = std::fs::rename(&old_dir, &new_dir) {
+            stored.id = old_id.to_string();
+            stored.save_to_dir(&old_dir)?;
+            return Err(e.into());
+        }
         
-        // 保存到缓存
-        if let Some(parent) = cache_path.parent() {
-            if let Err(e) = tokio::fs::create_dir_all(parent).await {
-                log::warn!("创建缓存目录失败 {:?}: {}", parent, e);
+        let old_cache_dir = self.config.static_dir.join(old_id);
+        let new_cache_dir = self.config.static_dir.join(new_id);
+        let mut cache_moved = false;
+        if old_cache_dir.exists() {
+            match std::fs::rename(&old_cache_dir, &new_cache_dir) {
+                Ok(()) => cache_moved = true,
+                Err(e) => log::warn!("重命名缓存目录失败 {:?}: {}", old_cache_dir, e),
             }
         }
+        self.rekey_subset_index(old_id, new_id, cache_moved.then_some((&old_cache_dir, &new_cache_dir)));
         
-        if let Err(e) = tokio::fs::write(&cache_path, &woff2_data).await {
-            log::warn!("保存缓存文件失败 {:?}: {}", cache_path, e);
-        } else {
-            log::info!("保存缓存文件: {:?}", cache_path);
+        if let Some(mut font_config) = fonts.remove(old_id) {
+            font_config.id = new_id.to_string();
+            font_config.dir = new_dir;
+            fonts.insert(new_id.to_string(), font_config);
+        }
+        let prefix = format!("{}:", old_id);
+        let renamed: Vec<String> = processors.keys().filter(|key| key.starts_with(&prefix)).cloned().collect();
+        for key in renamed {
+            if let Some(processor) = processors.remove(&key) {
+                processors.insert(format!("{}:{}", new_id, &key[prefix.len()..]), processor);
+            }
         }
         
-        Ok(woff2_data)
+        let referencing: Vec<&str> = fonts
+            .values()
+            .filter(|config| config.fallback.contains(&FallbackEntry::Local(old_id.to_string())))
+            .map(|config| config.id.as_str())
+            .collect();
+        if !referencing.is_empty() {
+            log::warn!("以下字体的回退配置仍引用旧ID {}: {}", old_id, referencing.join(", "));
+        }
+        drop(processors);
+        drop(fonts);
+        
+        // 缓存清单和启动时的固定链接索引优先使用扩展属性中的字体ID，需要一并更新
+        if cache_moved {
+            let (old_id, new_id) = (old_id.to_string(), new_id.to_string());
+            tokio::task::spawn_blocking(move || rewrite_cache_xattr_font_id(&new_cache_dir, &old_id, &new_id))
+                .await
+                .map_err(|e| AppError::InternalError(e.into()))?;
+        }
+        
+        log::info!("字体ID已修改: {} -> {}", old_id, new_id);
+        Ok(())
     }
     
-    /// 强制重新生成字体文件并缓存
-    pub async fn regenerate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<(), AppError> {
-        if let Some(id) = font_id {
-            // 为单个字符生成缓存
-            for &codepoint in codepoints {
-                let woff2_data = self.generate_font(Some(id), &[codepoint]).await?;
-                let cache_filename = generate_cache_filename(&[codepoint]);
-                let cache_path = self.config.static_dir.join(id).join(&cache_filename);
-                
-                if let Some(parent) = cache_path.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
-                }
-                
-                tokio::fs::write(&cache_path, &woff2_data).await?;
-                log::info!("重新生成缓存文件: {:?}", cache_path);
+    /// 字体改名后更新固定链接索引中的字体ID，缓存目录已移动时同时更新文件路径
+    fn rekey_subset_index(&self, old_id: &str, new_id: &str, moved: Option<(&Path, &Path)>) {
+        let repath = |path: PathBuf| match moved.and_then(|(from, to)| Some(to.join(path.strip_prefix(from).ok()?))) {
+            Some(moved_path) => moved_path,
+            None => path,
+        };
+        
+        let mut subset_hashes = self.subset_hashes.lock().unwrap();
+        let renamed: Vec<(String, String)> = subset_hashes.keys().filter(|(font_id, _)| font_id == old_id).cloned().collect();
+        for key in renamed {
+            if let Some(path) = subset_hashes.remove(&key) {
+                subset_hashes.insert((new_id.to_string(), key.1), repath(path));
             }
-        } else {
-            // 为所有字体生成缓存
-            let fonts = self.fonts.read().await;
-            for font_id in fonts.keys() {
-                let result = Box::pin(self.regenerate_font(Some(font_id), codepoints)).await;
-                if let Err(e) = result {
-                    log::warn!("重新生成字体缓存失败 {}: {}", font_id, e);
+        }
+        drop(subset_hashes);
+        
+        if let Some((from, _)) = moved {
+            let mut subset_meta = self.subset_meta.lock().unwrap();
+            let moved_paths: Vec<PathBuf> = subset_meta.keys().filter(|path| path.starts_with(from)).cloned().collect();
+            for path in moved_paths {
+                if let Some(meta) = subset_meta.remove(&path) {
+                    subset_meta.insert(repath(path), meta);
                 }
             }
         }
-        
-        Ok(())
     }
     
-    /// 启动定期清理任务
-    fn start_cleanup_task(&self) {
-        let static_dir = self.config.static_dir.clone();
-        let cleanup_days = self.config.cache_cleanup_days;
+    /// 合并字体所有文件支持的码点
+    async fn all_codepoints(&self, font_id: &str) -> Result<BTreeSet<u32>, AppError> {
+        let fonts = self.fonts.read().await;
+        let font_config = fonts
+            .get(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
         
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(24 * 3600)); // 每天执行一次
-            
-            loop {
-                interval.tick().await;
-                
+        let processors = self.processors.read().await;
+        let mut all_codepoints = BTreeSet::new();
+        for font_file in &font_config.files {
+            let key = format!("{}:{}", font_id, font_file.font_family);
+            if let Some(processor) = processors.get(&key) {
+                all_codepoints.extend(processor.get_all_codepoints());
+            }
+        }
+        Ok(all_codepoints)
+    }
+    
+    /// 以CSS `unicode-range` 格式返回字体支持的码点范围
+    pub async fn font_ranges(&self, font_id: &str) -> Result<Vec<String>, AppError> {
+        let codepoints: Vec<u32> = self.all_codepoints(font_id).await?.into_iter().collect();
+        Ok(format_unicode_ranges(&codepoints))
+    }
+    
+    /// 统计字体支持的emoji区块码点
+    pub async fn emoji_coverage(&self, font_id: &str) -> Result<EmojiCoverage, AppError> {
+        let all_codepoints = self.all_codepoints(font_id).await?;
+        let ranges: Vec<_> = EMOJI_BLOCKS.iter().filter_map(|&block| unicode_block_range(block)).collect();
+        
+        let codepoints: Vec<u32> = ranges
+            .iter()
+            .flat_map(|range| all_codepoints.range(range.clone()).copied())
+            .collect();
+        Ok(EmojiCoverage {
+            covered: codepoints.len(),
+            total: ranges.iter().map(|range| range.clone().count()).sum(),
+            codepoints,
+        })
+    }
+    
+    /// 按 `HEATMAP_CELL_SIZE` 个码点一格统计各CJK统一表意文字区块的覆盖率
+    pub async fn coverage_heatmap(&self, font_id: &str) -> Result<Vec<CoverageRow>, AppError> {
+        let codepoints = self.all_codepoints(font_id).await?;
+        
+        let rows = HAN_IDEOGRAPH_BLOCKS
+            .iter()
+            .filter_map(|&block| {
+                let range = unicode_block_range(block)?;
+                let cells = (*range.start()..=*range.end())
+                    .step_by(HEATMAP_CELL_SIZE as usize)
+                    .map(|start| {
+                        let end = (start + HEATMAP_CELL_SIZE - 1).min(*range.end());
+                        let covered = codepoints.range(start..=end).count() as f32;
+                        let percentage = covered * 100.0 / (end - start + 1) as f32;
+                        (percentage * 10.0).round() / 10.0
+                    })
+                    .collect();
+                Some(CoverageRow {
+                    block: block.to_string(),
+                    start: format!("U+{:04X}", range.start()),
+                    cells,
+                })
+            })
+            .collect();
+        
+        Ok(rows)
+    }
+    
+    /// 分页列出字体支持的所有码点，页码从1开始
+    pub async fn list_glyphs(&self, font_id: &str, page: usize, per_page: usize) -> Result<GlyphPage, AppError> {
+        if page == 0 || per_page == 0 {
+            return Err(AppError::ConfigError("分页参数必须大于0".to_string()));
+        }
+        
+        let all_codepoints = self.all_codepoints(font_id).await?;
+        let total = all_codepoints.len();
+        let codepoints = all_codepoints
+            .into_iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .collect();
+        
+        Ok(GlyphPage { codepoints, page, total })
+    }
+    
+    /// 生成字体WOFF2文件
+    pub async fn generate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<Vec<u8>, AppError> {
+        if codepoints.is_empty() {
+            return Err(AppError::CharacterNotFound(0));
+        }
+        
+        if !cfg!(feature = "subsetting") {
+            return Err(AppError::FontProcessingError("subsetting not compiled in".to_string()));
+        }
+        
+        // 如果指定了字体ID，直接使用该字体
+        if let Some(id) = font_id {
+            return self.generate_font_by_id(id, codepoints, true).await;
+        }
+        
+        // 否则使用第一个包含请求字符的字体，其余字符沿该字体的回退链查找
+        for &codepoint in codepoints {
+            if let Some((font_id, _)) = self.get_processor_for_codepoint(codepoint).await {
+                return self.generate_font_by_id(&font_id, codepoints, true).await;
+            }
+        }
+        
+        // 本地字体都不包含请求的字符时，返回第一个覆盖这些字符的外部回退字体地址
+        let fonts = self.fonts.read().await;
+        let mut font_ids: Vec<&String> = fonts.keys().collect();
+        font_ids.sort();
+        match font_ids.into_iter().find_map(|font_id| external_fallback_url(&fonts[font_id], codepoints)) {
+            Some(url) => Err(AppError::ExternalFallback(url)),
+            None => Err(AppError::CharacterNotFound(codepoints[0])),
+        }
+    }
+    
+    /// 按字体ID顺序查找第一个包含码点的字体文件，返回字体ID和对应的处理器
+    ///
+    /// 每个处理器只查询一次cmap，不尝试生成子集，替换字体不参与查找
+    pub async fn get_processor_for_codepoint(&self, codepoint: u32) -> Option<(String, Arc<FontProcessor>)> {
+        let fonts = self.fonts.read().await;
+        let processors = self.processors.read().await;
+        let mut font_ids: Vec<&String> = fonts.keys().collect();
+        font_ids.sort();
+        font_ids.into_iter().find_map(|font_id| {
+            fonts[font_id]
+                .files
+                .iter()
+                .filter_map(|font_file| processors.get(&format!("{}:{}", font_id, font_file.font_family)))
+                .find(|processor| processor.contains_char(codepoint))
+                .map(|processor| (font_id.clone(), processor.clone()))
+        })
+    }
+    
+    /// 根据字体ID生成WOFF2文件，按深度优先顺序依次尝试fallback字体
+    ///
+    /// 本地字体都不包含请求的字符时，返回第一个覆盖这些字符的外部回退字体地址。
+    /// `preserve_hints` 为 `false` 时去掉TrueType指令和CFF hint
+    async fn generate_font_by_id(
+        &self,
+        font_id: &str,
+        codepoints: &[u32],
+        preserve_hints: bool,
+    ) -> Result<Vec<u8>, AppError> {
+        // 只在读锁内确定候选字体文件，重试等待期间不持有锁，避免阻塞排队的写操作
+        let (candidates, external_url) = self.subset_candidates(font_id, codepoints).await?;
+        let retries = self.config.subset_retry_count;
+        for candidate in candidates {
+            match candidate {
+                SubsetCandidate::Single(key, processor) => {
+                    if let Some(woff2_data) = subset_available(&key, &processor, codepoints, preserve_hints, retries).await {
+                        return Ok(woff2_data);
+                    }
+                }
+                SubsetCandidate::Merged(key, parts) => {
+                    return with_retries(&key, retries, || {
+                        let ttf_data = merge_subsets(&parts, preserve_hints)?;
+                        FontProcessor::ttf_to_woff2(&ttf_data).map_err(|e| AppError::FontProcessingError(e.to_string()))
+                    })
+                    .await;
+                }
+            }
+        }
+        
+        match external_url {
+            Some(url) => Err(AppError::ExternalFallback(url)),
+            None => Err(AppError::CharacterNotFound(codepoints[0])),
+        }
+    }
+    
+    /// 按深度优先的回退顺序列出生成子集时依次尝试的字体文件，以及第一个覆盖请求字符的外部回退字体地址
+    ///
+    /// 某个字体的替换字体包含请求字符时，合并子集是最后一个候选
+    async fn subset_candidates(
+        &self,
+        font_id: &str,
+        codepoints: &[u32],
+    ) -> Result<(Vec<SubsetCandidate>, Option<String>), AppError> {
+        let fonts = self.fonts.read().await;
+        if !fonts.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let processors = self.processors.read().await;
+        let mut candidates = Vec::new();
+        let mut visited = HashSet::new();
+        let mut external_url = None;
+        
+        // 栈中保存从请求字体到当前字体的回退路径，以及还允许继续回退的层数
+        let mut stack = vec![(vec![font_id.to_string()], self.config.max_fallback_depth)];
+        while let Some((path, remaining_depth)) = stack.pop() {
+            let Some(current_id) = path.last() else {
+                continue;
+            };
+            if !visited.insert(current_id.clone()) {
+                continue;
+            }
+            let Some(font_config) = fonts.get(current_id) else {
+                log::warn!("回退字体不存在: {}", current_id);
+                continue;
+            };
+            
+            // 替换字体中对应区块的字形优先，每个码点使用第一个包含它的替换字体
+            let mut remaining = codepoints.to_vec();
+            let mut overrides = Vec::new();
+            for block in &font_config.overrides {
+                let key = override_key(current_id, block);
+                let (Some(processor), Some(range)) = (processors.get(&key), unicode_block_range(block)) else {
+                    continue;
+                };
+                let (claimed, rest): (Vec<u32>, Vec<u32>) = remaining
+                    .into_iter()
+                    .partition(|&cp| range.contains(&cp) && processor.contains_char(cp));
+                remaining = rest;
+                if !claimed.is_empty() {
+                    overrides.push((current_id.clone(), processor.clone(), claimed));
+                }
+            }
+            if !overrides.is_empty() {
+                // 其余字符仍由主字体提供，替换字体的字形追加到主字体的子集中
+                let main = font_config
+                    .files
+                    .iter()
+                    .filter_map(|font_file| processors.get(&format!("{}:{}", current_id, font_file.font_family)))
+                    .find_map(|processor| {
+                        let available = processor.get_available_chars(&remaining);
+                        (!available.is_empty()).then(|| (current_id.clone(), processor.clone(), available))
+                    });
+                let parts = main.into_iter().chain(overrides).collect();
+                candidates.push(SubsetCandidate::Merged(current_id.clone(), parts));
+                return Ok((candidates, external_url));
+            }
+            
+            // 依次尝试每个字体文件，直到找到包含字符的文件
+            for font_file in &font_config.files {
+                let key = format!("{}:{}", current_id, font_file.font_family);
+                if let Some(processor) = processors.get(&key) {
+                    candidates.push(SubsetCandidate::Single(key, processor.clone()));
+                }
+            }
+            
+            // 如果当前字体不包含字符，尝试fallback字体
+            if remaining_depth == 0 {
+                if !font_config.fallback.is_empty() {
+                    log::warn!("超过最大回退深度 {}: {}", self.config.max_fallback_depth, path.join(" -> "));
+                }
+                continue;
+            }
+            // 记录第一个覆盖请求字符的外部字体，本地回退都失败时使用
+            if external_url.is_none() {
+                external_url = external_fallback_url(font_config, codepoints);
+            }
+            for entry in font_config.fallback.iter().rev() {
+                let FallbackEntry::Local(fallback_id) = entry else {
+                    continue;
+                };
+                let mut next = path.clone();
+                next.push(fallback_id.clone());
+                // 内置字体之间互为回退是正常配置，循环处只跳过已尝试的字体
+                if path.contains(fallback_id) {
+                    log::debug!("字体回退存在循环: {}", next.join(" -> "));
+                    continue;
+                }
+                stack.push((next, remaining_depth - 1));
+            }
+        }
+        
+        Ok((candidates, external_url))
+    }
+    
+    /// 按与生成字体相同的回退顺序，把每个码点分配给第一个包含它的字体
+    ///
+    /// 只返回分配到码点的字体，所有字体都不包含的码点会被忽略
+    pub async fn fallback_sources(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<(String, Vec<u32>)>, AppError> {
+        let fonts = self.fonts.read().await;
+        if !fonts.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let processors = self.processors.read().await;
+        let max_depth = self.config.max_fallback_depth;
+        let mut visited = HashSet::new();
+        let mut remaining: Vec<u32> = codepoints.to_vec();
+        let mut sources = Vec::new();
+        
+        let mut stack = vec![vec![font_id.to_string()]];
+        while let Some(path) = stack.pop() {
+            if remaining.is_empty() {
+                break;
+            }
+            let Some(current_id) = path.last() else {
+                continue;
+            };
+            if !visited.insert(current_id.clone()) {
+                continue;
+            }
+            let Some(font_config) = fonts.get(current_id) else {
+                continue;
+            };
+            
+            let font_processors: Vec<&Arc<FontProcessor>> = font_config
+                .files
+                .iter()
+                .filter_map(|font_file| processors.get(&format!("{}:{}", current_id, font_file.font_family)))
+                .collect();
+            let (claimed, rest): (Vec<u32>, Vec<u32>) = remaining
+                .into_iter()
+                .partition(|&cp| font_processors.iter().any(|processor| processor.contains_char(cp)));
+            remaining = rest;
+            if !claimed.is_empty() {
+                sources.push((current_id.clone(), claimed));
+            }
+            
+            if path.len() > max_depth {
+                continue;
+            }
+            for entry in font_config.fallback.iter().rev() {
+                let FallbackEntry::Local(fallback_id) = entry else {
+                    continue;
+                };
+                if path.contains(fallback_id) {
+                    continue;
+                }
+                let mut next = path.clone();
+                next.push(fallback_id.clone());
+                stack.push(next);
+            }
+        }
+        
+        Ok(sources)
+    }
+    
+    /// 把 `fallback_sources` 分配给多个字体的码点合并为一个字体，`ttf` 为 `false` 时返回WOFF2
+    ///
+    /// 每个码点使用所在字体中第一个包含它的文件，合并结果不写入缓存
+    pub async fn get_merged_font(
+        &self,
+        sources: &[(String, Vec<u32>)],
+        ttf: bool,
+        preserve_hints: bool,
+    ) -> Result<CachedFont, AppError> {
+        let parts = {
+            let fonts = self.fonts.read().await;
+            let processors = self.processors.read().await;
+            let mut parts = Vec::new();
+            for (font_id, codepoints) in sources {
+                let font_config = fonts.get(font_id).ok_or_else(|| AppError::FontNotFound(font_id.clone()))?;
+                let mut remaining = codepoints.clone();
+                for font_file in &font_config.files {
+                    let Some(processor) = processors.get(&format!("{}:{}", font_id, font_file.font_family)) else {
+                        continue;
+                    };
+                    let (claimed, rest): (Vec<u32>, Vec<u32>) =
+                        remaining.into_iter().partition(|&cp| processor.contains_char(cp));
+                    remaining = rest;
+                    if !claimed.is_empty() {
+                        parts.push((font_id.clone(), processor.clone(), claimed));
+                    }
+                }
+            }
+            parts
+        };
+        if parts.is_empty() {
+            let codepoint = sources.iter().find_map(|(_, codepoints)| codepoints.first().copied());
+            return Err(AppError::CharacterNotFound(codepoint.unwrap_or(0)));
+        }
+        
+        let mut data = merge_subsets(&parts, preserve_hints)?;
+        if !ttf {
+            data = FontProcessor::ttf_to_woff2(&data).map_err(|e| AppError::FontProcessingError(e.to_string()))?;
+        }
+        let meta = SubsetMeta::compute(&data, None);
+        Ok(CachedFont {
+            data,
+            modified: None,
+            hash: meta.hash,
+            content_digest: meta.content_digest,
+            glyph_count: meta.glyph_count,
+        })
+    }
+    
+    /// 找出请求字体及其本地回退字体都不包含、会显示为缺字方框（豆腐块）的码点
+    ///
+    /// 外部回退字体不计入，字体不存在时所有码点都视为缺字
+    pub async fn detect_tofu(&self, font_id: &str, codepoints: &[u32]) -> Vec<u32> {
+        let covered: HashSet<u32> = self
+            .fallback_sources(font_id, codepoints)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|(_, claimed)| claimed)
+            .collect();
+        codepoints.iter().copied().filter(|cp| !covered.contains(cp)).collect()
+    }
+    
+    /// 获取或生成缓存的字体文件
+    pub async fn get_cached_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        let cache_filename = generate_cache_filename(codepoints);
+        let cache_path = self.config.static_dir.join(font_id).join(&cache_filename);
+        
+        // 检查缓存是否存在
+        if let Some(cached) = self.read_cached_or_pending(&cache_path).await {
+            return Ok(cached);
+        }
+        
+        // 同一缓存文件同时只有一个请求在生成，其余请求等待后直接读取缓存
+        let entry = GeneratingEntry::acquire(&self.generating, &cache_path);
+        let result = {
+            let _guard = entry.lock.lock().await;
+            match self.read_cached_or_pending(&cache_path).await {
+                Some(cached) => Ok(cached),
+                None => self.generate_cached_font(font_id, codepoints, &cache_path).await,
+            }
+        };
+        
+        if let Ok(cached) = &result {
+            self.record_subset_hash(font_id, &cache_path, &cached.hash);
+        }
+        
+        result
+    }
+    
+    /// 获取或生成TTF格式的子集，只使用字体自身第一个包含请求字符的文件，不查找回退字体
+    pub async fn get_cached_ttf(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        let processor = self.first_covering_processor(font_id, codepoints).await?;
+        
+        let cache_path = self.config.static_dir.join(font_id).join(generate_ttf_cache_filename(codepoints));
+        if let Ok(stored) = tokio::fs::read(&cache_path).await {
+            match decode_ttf_cache(&stored) {
+                Ok(data) => {
+                    let modified = file_modified(&cache_path).await;
+                    return Ok(self.cached_subset(&cache_path, data, modified));
+                }
+                Err(e) => log::warn!("读取TTF缓存失败 {:?}: {}", cache_path, e),
+            }
+        }
+        
+        let requested = codepoints.to_vec();
+        let ttf_data = tokio::task::spawn_blocking(move || processor.subset_font(&requested))
+            .await
+            .map_err(|e| AppError::InternalError(e.into()))?
+            .map_err(|e| AppError::FontProcessingError(e.to_string()))?;
+        
+        let modified = if write_cache_file(&cache_path, font_id, codepoints, &encode_ttf_cache(&ttf_data)?).await {
+            file_modified(&cache_path).await
+        } else {
+            None
+        };
+        Ok(self.cached_subset(&cache_path, ttf_data, modified))
+    }
+    
+    /// 获取去掉hinting的WOFF2子集，与 `get_cached_font` 一样查找回退字体和替换字体
+    pub async fn get_cached_unhinted_font(&self, font_id: &str, codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        let cache_path = self.config.static_dir.join(font_id).join(generate_unhinted_cache_filename(codepoints));
+        if let Ok(data) = tokio::fs::read(&cache_path).await {
+            let cached = self.cached_subset(&cache_path, data, file_modified(&cache_path).await);
+            self.record_subset_hash(font_id, &cache_path, &cached.hash);
+            return Ok(cached);
+        }
+        
+        if codepoints.is_empty() {
+            return Err(AppError::CharacterNotFound(0));
+        }
+        if !cfg!(feature = "subsetting") {
+            return Err(AppError::FontProcessingError("subsetting not compiled in".to_string()));
+        }
+        let woff2_data = self.generate_font_by_id(font_id, codepoints, false).await?;
+        
+        let modified = if write_cache_file(&cache_path, font_id, codepoints, &woff2_data).await {
+            file_modified(&cache_path).await
+        } else {
+            None
+        };
+        let cached = self.cached_subset(&cache_path, woff2_data, modified);
+        self.record_subset_hash(font_id, &cache_path, &cached.hash);
+        Ok(cached)
+    }
+    
+    /// 按WOFF2内容的BLAKE3哈希读取缓存子集，与生成它的码点无关
+    ///
+    /// 文件已被清理或字体更新后内容变化时，移除索引条目并返回 `CacheNotFound`
+    pub async fn get_subset_by_hash(&self, font_id: &str, hash: &str) -> Result<CachedFont, AppError> {
+        if !self.fonts.read().await.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let key = (font_id.to_string(), hash.to_ascii_lowercase());
+        let cache_path = self
+            .subset_hashes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| AppError::CacheNotFound(hash.to_string()))?;
+        
+        match self.read_cached_or_pending(&cache_path).await {
+            Some(cached) if cached.hash == key.1 => Ok(cached),
+            _ => {
+                self.subset_hashes.lock().unwrap().remove(&key);
+                Err(AppError::CacheNotFound(hash.to_string()))
+            }
+        }
+    }
+    
+    fn record_subset_hash(&self, font_id: &str, cache_path: &Path, hash: &str) {
+        let key = (font_id.to_string(), hash.to_string());
+        self.subset_hashes.lock().unwrap().insert(key, cache_path.to_path_buf());
+    }
+    
+    /// 为缓存文件附上哈希、摘要和字形数量，同一路径的修改时间和大小不变时复用已计算的结果
+    ///
+    /// 计算字形数量需要解压WOFF2，每个文件只在生成或重启后第一次读取时计算一次
+    fn cached_subset(&self, cache_path: &Path, data: Vec<u8>, modified: Option<SystemTime>) -> CachedFont {
+        let known = self
+            .subset_meta
+            .lock()
+            .unwrap()
+            .get(cache_path)
+            .filter(|meta| meta.modified == modified && meta.len == data.len())
+            .cloned();
+        let meta = known.unwrap_or_else(|| {
+            let meta = SubsetMeta::compute(&data, modified);
+            self.subset_meta.lock().unwrap().insert(cache_path.to_path_buf(), meta.clone());
+            meta
+        });
+        CachedFont {
+            data,
+            modified,
+            hash: meta.hash,
+            content_digest: meta.content_digest,
+            glyph_count: meta.glyph_count,
+        }
+    }
+    
+    /// 在后台计算静态目录中已有WOFF2子集的哈希，重启后固定链接仍然可用
+    fn index_cached_subsets(&self) {
+        let static_dir = self.config.static_dir.clone();
+        let subset_hashes = self.subset_hashes.clone();
+        tokio::task::spawn_blocking(move || {
+            let entries = list_cache_entries(&static_dir);
+            for entry in &entries {
+                let path = static_dir.join(&entry.path);
+                let Ok(data) = std::fs::read(&path) else {
+                    continue;
+                };
+                let key = (entry.font_id.clone(), generate_file_hash(&data));
+                subset_hashes.lock().unwrap().entry(key).or_insert(path);
+            }
+            log::info!("已索引 {} 个缓存子集的哈希", entries.len());
+        });
+    }
+    
+    /// 字体自身文件中第一个包含请求字符的文件的处理器
+    async fn first_covering_processor(&self, font_id: &str, codepoints: &[u32]) -> Result<Arc<FontProcessor>, AppError> {
+        let fonts = self.fonts.read().await;
+        let font_config = fonts
+            .get(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+        let processors = self.processors.read().await;
+        font_config
+            .files
+            .iter()
+            .filter_map(|font_file| processors.get(&format!("{}:{}", font_id, font_file.font_family)))
+            .find(|processor| !processor.get_available_chars(codepoints).is_empty())
+            .cloned()
+            .ok_or(AppError::CharacterNotFound(codepoints.first().copied().unwrap_or(0)))
+    }
+    
+    /// 按字体自身文件中第一个包含请求字符的文件估算子集大小，误差约±30%
+    pub async fn estimate_subset_size(&self, font_id: &str, codepoints: &[u32]) -> Result<usize, AppError> {
+        let fonts = self.fonts.read().await;
+        let font_config = fonts
+            .get(font_id)
+            .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+        
+        let processors = self.processors.read().await;
+        for font_file in &font_config.files {
+            let key = format!("{}:{}", font_id, font_file.font_family);
+            let Some(processor) = processors.get(&key) else {
+                continue;
+            };
+            let available = processor.get_available_chars(codepoints).len();
+            if available > 0 {
+                return Ok(available * processor.average_glyph_size());
+            }
+        }
+        
+        Err(AppError::CharacterNotFound(codepoints.first().copied().unwrap_or(0)))
+    }
+    
+    /// 用字体自身第一个包含该字符的文件渲染字形PNG，不查找回退字体
+    pub async fn glyph_png(&self, font_id: &str, codepoint: u32, size: u32) -> Result<Vec<u8>, AppError> {
+        let processor = {
+            let fonts = self.fonts.read().await;
+            let font_config = fonts
+                .get(font_id)
+                .ok_or_else(|| AppError::FontNotFound(font_id.to_string()))?;
+            let processors = self.processors.read().await;
+            font_config
+                .files
+                .iter()
+                .filter_map(|font_file| processors.get(&format!("{}:{}", font_id, font_file.font_family)))
+                .find(|processor| processor.contains_char(codepoint))
+                .cloned()
+                .ok_or(AppError::CharacterNotFound(codepoint))?
+        };
+        
+        tokio::task::spawn_blocking(move || processor.glyph_png(codepoint, size))
+            .await
+            .map_err(|e| AppError::InternalError(e.into()))?
+            .map_err(|e| AppError::FontProcessingError(e.to_string()))
+    }
+    
+    /// 只读取缓存子集开头的文件头，不生成缺失的缓存
+    pub async fn woff2_header(&self, font_id: &str, codepoints: &[u32]) -> Result<Woff2Header, AppError> {
+        if !self.fonts.read().await.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let cache_filename = generate_cache_filename(codepoints);
+        let cache_path = self.config.static_dir.join(font_id).join(&cache_filename);
+        let mut file = match tokio::fs::File::open(&cache_path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(AppError::CacheNotFound(format!("{}/{}", font_id, cache_filename)));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        
+        let mut header = [0u8; Woff2Header::SIZE];
+        file.read_exact(&mut header)
+            .await
+            .map_err(|_| AppError::FontProcessingError("缓存文件不完整".to_string()))?;
+        Woff2Header::parse(&header).map_err(|e| AppError::FontProcessingError(e.to_string()))
+    }
+    
+    /// 读取写入缓冲区中尚未落盘的文件或磁盘上的缓存文件
+    ///
+    /// 文件写完才会移出缓冲区，先查缓冲区可以避免读到写了一半的文件
+    async fn read_cached_or_pending(&self, cache_path: &Path) -> Option<CachedFont> {
+        let (data, modified) = match self.write_batcher.pending(cache_path) {
+            Some((data, queued_at)) => (data, Some(queued_at)),
+            None => read_cached_font(cache_path).await?,
+        };
+        Some(self.cached_subset(cache_path, data, modified))
+    }
+    
+    /// 生成新的字体文件并放入写入缓冲区，缓冲区已满时直接写入
+    async fn generate_cached_font(
+        &self,
+        font_id: &str,
+        codepoints: &[u32],
+        cache_path: &Path,
+    ) -> Result<CachedFont, AppError> {
+        let woff2_data = self.generate_font(Some(font_id), codepoints).await?;
+        
+        if self.write_batcher.submit(cache_path, font_id, codepoints, woff2_data.clone()) {
+            return Ok(self.cached_subset(cache_path, woff2_data, Some(SystemTime::now())));
+        }
+        
+        let modified = if write_cache_file(cache_path, font_id, codepoints, &woff2_data).await {
+            file_modified(cache_path).await
+        } else {
+            None
+        };
+        
+        Ok(self.cached_subset(cache_path, woff2_data, modified))
+    }
+    
+    /// 把字体ID和码点写入缓存文件的扩展属性，便于不依赖目录结构反查，非Linux平台或文件系统不支持时忽略
+    pub fn write_cache_xattr(path: &Path, font_id: &str, codepoints: &[u32]) {
+        #[cfg(target_os = "linux")]
+        {
+            let codepoints = codepoints.iter().map(|cp| cp.to_string()).collect::<Vec<_>>().join(",");
+            for (name, value) in [(XATTR_FONT_ID, font_id), (XATTR_CODEPOINTS, codepoints.as_str())] {
+                if let Err(e) = xattr::set(path, name, value.as_bytes()) {
+                    log::debug!("写入扩展属性失败 {:?}: {}", path, e);
+                    return;
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (path, font_id, codepoints);
+    }
+    
+    /// 强制重新生成字体文件并缓存
+    pub async fn regenerate_font(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<(), AppError> {
+        if let Some(id) = font_id {
+            // 为单个字符生成缓存
+            for &codepoint in codepoints {
+                let woff2_data = self.generate_font(Some(id), &[codepoint]).await?;
+                let cache_filename = generate_cache_filename(&[codepoint]);
+                let cache_path = self.config.static_dir.join(id).join(&cache_filename);
+                
+                store_cache_file(&cache_path, id, &[codepoint], &woff2_data).await?;
+                log::info!("重新生成缓存文件: {:?}", cache_path);
+            }
+        } else {
+            // 为所有字体生成缓存
+            let fonts = self.fonts.read().await;
+            for font_id in fonts.keys() {
+                let result = Box::pin(self.regenerate_font(Some(font_id), codepoints)).await;
+                if let Err(e) = result {
+                    log::warn!("重新生成字体缓存失败 {}: {}", font_id, e);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// 重新生成指定码点集合的子集，指定字体时覆盖对应的缓存文件
+    pub async fn regenerate_subset(&self, font_id: Option<&str>, codepoints: &[u32]) -> Result<Vec<u8>, AppError> {
+        let woff2_data = self.generate_font(font_id, codepoints).await?;
+        
+        if let Some(id) = font_id {
+            let cache_path = self.config.static_dir.join(id).join(generate_cache_filename(codepoints));
+            store_cache_file(&cache_path, id, codepoints, &woff2_data).await?;
+            log::info!("重新生成缓存文件: {:?}", cache_path);
+        }
+        
+        Ok(woff2_data)
+    }
+    
+    /// 根据缓存文件名还原码点，将字体的所有缓存子集提交到后台重新生成，返回实际提交的任务数量
+    pub async fn rebuild_cache(&self, font_id: &str) -> Result<usize, AppError> {
+        if !self.fonts.read().await.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let font_dir = self.config.static_dir.join(font_id);
+        let codepoint_sets = tokio::task::spawn_blocking(move || cached_codepoint_sets(&font_dir))
+            .await
+            .map_err(|e| AppError::InternalError(e.into()))?;
+        
+        // 队列已满时等待后台任务处理，只统计实际提交的任务
+        let mut submitted = 0;
+        for codepoints in codepoint_sets {
+            if let Err(e) = self.subsetter.submit_rebuild(font_id.to_string(), codepoints).await {
+                log::warn!("提交缓存重建任务失败 {}: {}", font_id, e);
+                break;
+            }
+            submitted += 1;
+        }
+        log::info!("已提交 {} 的 {} 个缓存重建任务", font_id, submitted);
+        
+        Ok(submitted)
+    }
+    
+    /// 列出静态目录中的所有缓存子集，按路径排序
+    pub fn cache_entries(&self) -> Vec<CacheManifestEntry> {
+        list_cache_entries(&self.config.static_dir)
+    }
+    
+    /// 统计字体缓存目录中的WOFF2子集数量，不包括尚在写入缓冲区中的文件
+    pub async fn count_subsets(&self, font_id: &str) -> Result<usize, AppError> {
+        if !self.fonts.read().await.contains_key(font_id) {
+            return Err(AppError::FontNotFound(font_id.to_string()));
+        }
+        
+        let font_dir = self.config.static_dir.join(font_id);
+        let count = WalkDir::new(font_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "woff2"))
+            .count();
+        Ok(count)
+    }
+    
+    /// 将所有缓存子集的清单写入JSON文件
+    pub async fn export_cache_manifest(&self, output_path: &Path) -> Result<()> {
+        let manifest = CacheManifest {
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            entries: self.cache_entries(),
+        };
+        tokio::fs::write(output_path, serde_json::to_string_pretty(&manifest)?).await?;
+        log::info!("导出缓存清单: {:?}，共 {} 个文件", output_path, manifest.entries.len());
+        
+        Ok(())
+    }
+    
+    /// 重新解析所有字体文件，报告损坏或缺失的字体
+    pub async fn verify_all_fonts(&self) -> Result<FontVerifyReport, AppError> {
+        let mut configs: Vec<FontConfig> = self.fonts.read().await.values().cloned().collect();
+        configs.sort_by(|a, b| a.id.cmp(&b.id));
+        
+        let report = tokio::task::spawn_blocking(move || {
+            let mut report = FontVerifyReport {
+                ok: Vec::new(),
+                failed: Vec::new(),
+            };
+            
+            for config in configs {
+                let error = config.files.iter().find_map(|font_file| {
+                    let font_path = config.dir.join(&font_file.path);
+                    FontProcessor::verify_file(&font_path)
+                        .err()
+                        .map(|e| format!("{}: {}", font_file.path, e))
+                });
+                
+                match error {
+                    Some(error) => {
+                        log::error!("字体校验失败 {}: {}", config.id, error);
+                        report.failed.push(FontVerifyFailure { id: config.id, error });
+                    }
+                    None => report.ok.push(config.id),
+                }
+            }
+            report
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.into()))?;
+        
+        Ok(report)
+    }
+    
+    /// 将同一字体缓存目录中内容相同的缓存文件替换为指向同一文件的硬链接
+    ///
+    /// 不同的码点组合可能生成相同的WOFF2文件，例如请求中包含字体没有的字符。
+    /// 硬链接共享扩展属性，不同字体的文件即使内容相同也不链接，以免记录的字体ID错误。
+    /// 硬链接失败（如跨文件系统）的文件保持不变。缓存文件都通过 `store_cache_file` 重命名替换，
+    /// 重新生成其中一个文件不会改写其他链接
+    pub async fn compact_cache(&self) -> Result<CacheCompactReport, AppError> {
+        let paths: Vec<(String, PathBuf)> = self
+            .cache_entries()
+            .into_iter()
+            .map(|entry| {
+                let font_dir = entry.path.split('/').next().unwrap_or_default().to_string();
+                (font_dir, self.config.static_dir.join(entry.path))
+            })
+            .collect();
+        
+        let report = tokio::task::spawn_blocking(move || {
+            let mut report = CacheCompactReport {
+                files: paths.len(),
+                linked: 0,
+                saved_bytes: 0,
+            };
+            
+            // 按字体缓存目录和内容哈希分组，每组保留第一个文件
+            let mut originals: HashMap<(String, String), PathBuf> = HashMap::new();
+            for (font_dir, path) in paths {
+                let Ok(data) = std::fs::read(&path) else {
+                    continue;
+                };
+                let key = (font_dir, generate_file_hash(&data));
+                let Some(original) = originals.get(&key) else {
+                    originals.insert(key, path);
+                    continue;
+                };
+                if is_same_file(original, &path) {
+                    continue;
+                }
+                
+                // 先链接到临时文件再替换，失败时原文件不受影响
+                let temp_path = path.with_extension("woff2.link");
+                if let Err(e) = std::fs::hard_link(original, &temp_path) {
+                    log::warn!("创建硬链接失败 {:?}: {}", path, e);
+                    continue;
+                }
+                if let Err(e) = std::fs::rename(&temp_path, &path) {
+                    log::warn!("替换重复缓存文件失败 {:?}: {}", path, e);
+                    let _ = std::fs::remove_file(&temp_path);
+                    continue;
+                }
+                report.linked += 1;
+                report.saved_bytes += data.len() as u64;
+            }
+            report
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.into()))?;
+        
+        log::info!(
+            "缓存去重完成: 扫描 {} 个文件，链接 {} 个，节省 {} 字节",
+            report.files, report.linked, report.saved_bytes
+        );
+        Ok(report)
+    }
+    
+    /// 记录一次字体请求中的码点
+    pub fn record_request(&self, codepoints: &[u32]) {
+        let mut counts = self.request_counts.lock().unwrap();
+        for &codepoint in codepoints {
+            *counts.entry(codepoint).or_insert(0) += 1;
+        }
+    }
+    
+    /// 记录一次返回了指定字体子集的请求
+    pub fn record_font_served(&self, font_id: &str) {
+        *self.font_request_counts.lock().unwrap().entry(font_id.to_string()).or_insert(0) += 1;
+    }
+    
+    /// 自启动以来请求次数最多的N个字体，按次数降序排列，次数相同时按字体ID排序
+    pub fn most_used_fonts(&self, top_n: usize) -> Vec<(String, u64)> {
+        let mut counts: Vec<(String, u64)> = self
+            .font_request_counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(font_id, &count)| (font_id.clone(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(top_n);
+        counts
+    }
+    
+    /// 请求次数最多的N个码点
+    fn top_codepoints(&self, n: usize) -> Vec<u32> {
+        let counts = self.request_counts.lock().unwrap();
+        let mut sorted: Vec<(u32, u64)> = counts.iter().map(|(&cp, &count)| (cp, count)).collect();
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        sorted.into_iter().take(n).map(|(cp, _)| cp).collect()
+    }
+    
+    fn load_request_counts(&self) {
+        let path = self.config.data_dir.join(REQUEST_COUNTS_FILENAME);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return;
+        };
+        match serde_json::from_str::<HashMap<u32, u64>>(&content) {
+            Ok(counts) => *self.request_counts.lock().unwrap() = counts,
+            Err(e) => log::warn!("读取码点请求统计失败 {:?}: {}", path, e),
+        }
+    }
+    
+    fn save_request_counts(&self) {
+        let path = self.config.data_dir.join(REQUEST_COUNTS_FILENAME);
+        let result = serde_json::to_string(&*self.request_counts.lock().unwrap())
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(std::fs::write(&path, content)?));
+        if let Err(e) = result {
+            log::warn!("保存码点请求统计失败 {:?}: {}", path, e);
+        }
+    }
+    
+    /// 在控制台打印已加载字体的ID、版本、字形数量和fallback链，方便运维确认字体是否正确加载
+    pub async fn print_startup_summary(&self) {
+        let fonts = self.fonts.read().await;
+        let processors = self.processors.read().await;
+        let mut infos: Vec<FontInfo> = fonts.values().map(|config| Self::font_info(config, &processors)).collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        
+        let header = ["ID", "版本", "字形数", "Fallback"];
+        let rows: Vec<[String; 4]> = infos
+            .iter()
+            .map(|info| {
+                let fallback = info
+                    .fallback
+                    .iter()
+                    .map(|entry| match entry {
+                        FallbackEntry::Local(id) => id.clone(),
+                        FallbackEntry::External(external) => external.url_pattern.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                [info.id.clone(), info.version.clone(), info.glyph_count.to_string(), fallback]
+            })
+            .collect();
+        
+        let mut widths = header.map(display_width);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(display_width(cell));
+            }
+        }
+        let format_row = |cells: [&str; 4]| {
+            cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - display_width(cell))))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+        
+        let mut table = format!("已加载 {} 个字体:\n{}\n", rows.len(), format_row(header));
+        let separator = widths.map(|width| "-".repeat(width));
+        table.push_str(&format_row(separator.each_ref().map(String::as_str)));
+        for row in &rows {
+            table.push('\n');
+            table.push_str(&format_row(row.each_ref().map(String::as_str)));
+        }
+        println!("{}", table);
+    }
+    
+    /// 按配置的策略预热单字符缓存
+    pub async fn warm_up(&self) {
+        let codepoints = match &self.config.warmup {
+            WarmupStrategy::None => return,
+            WarmupStrategy::FrequencyFile(path) => match tokio::fs::read_to_string(path).await {
+                Ok(content) => parse_charset_file(&content),
+                Err(e) => {
+                    log::error!("读取预热字符文件失败 {:?}: {}", path, e);
+                    return;
+                }
+            },
+            WarmupStrategy::Preset(preset) => preset.codepoints(),
+            WarmupStrategy::TopN(n) => self.top_codepoints(*n),
+        };
+        
+        log::info!("开始预热缓存: {:?}，共 {} 个码点", self.config.warmup, codepoints.len());
+        let font_ids: Vec<String> = self.fonts.read().await.keys().cloned().collect();
+        let mut generated = 0;
+        for font_id in &font_ids {
+            for &codepoint in &codepoints {
+                let cache_path = self
+                    .config
+                    .static_dir
+                    .join(font_id)
+                    .join(generate_cache_filename(&[codepoint]));
+                if cache_path.exists() {
+                    continue;
+                }
+                match self.get_cached_font(font_id, &[codepoint]).await {
+                    Ok(_) => generated += 1,
+                    Err(e) => log::debug!("预热失败 {} {}: {}", font_id, codepoint, e),
+                }
+            }
+        }
+        log::info!("缓存预热完成，生成 {} 个文件", generated);
+    }
+    
+    /// 停止后台任务，应在服务器处理完所有请求后调用
+    pub fn shutdown(&self) {
+        self.save_request_counts();
+        self.subsetter.stop();
+        if let Some(task) = &self.cleanup_task {
+            task.abort();
+            log::info!("已停止缓存清理任务");
+        }
+    }
+    
+    /// 启动定期清理任务
+    fn start_cleanup_task(&self) -> JoinHandle<()> {
+        let static_dir = self.config.static_dir.clone();
+        let cleanup_days = self.config.cache_cleanup_days;
+        
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(24 * 3600)); // 每天执行一次
+            
+            loop {
+                interval.tick().await;
+                
                 log::info!("开始清理过期缓存文件");
                 
                 // 清理每个字体目录下的cache文件夹
@@ -268,6 +1938,430 @@ impl FontService {
                     }
                 }
             }
+        })
+    }
+}
+
+/// 正在生成的缓存文件对应的锁，离开作用域时没有其他请求等待就移除条目
+///
+/// 客户端断开导致请求被取消时同样会移除，不会在表中留下无人持有的条目
+struct GeneratingEntry<'a> {
+    generating: &'a Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    cache_path: PathBuf,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl<'a> GeneratingEntry<'a> {
+    fn acquire(generating: &'a Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>, cache_path: &Path) -> Self {
+        let lock = generating
+            .lock()
+            .unwrap()
+            .entry(cache_path.to_path_buf())
+            .or_default()
+            .clone();
+        Self {
+            generating,
+            cache_path: cache_path.to_path_buf(),
+            lock,
+        }
+    }
+}
+
+impl Drop for GeneratingEntry<'_> {
+    fn drop(&mut self) {
+        // 表中和当前条目各持有一份，没有其他等待者
+        if let Ok(mut generating) = self.generating.lock() {
+            if Arc::strong_count(&self.lock) == 2 {
+                generating.remove(&self.cache_path);
+            }
+        }
+    }
+}
+
+/// 替换字体在处理器表中的键
+fn override_key(font_id: &str, block: &str) -> String {
+    format!("{}:{}/{}", font_id, OVERRIDES_DIR, block)
+}
+
+/// 字体回退链中第一个覆盖请求字符的外部字体地址
+fn external_fallback_url(font_config: &FontConfig, codepoints: &[u32]) -> Option<String> {
+    font_config.fallback.iter().find_map(|entry| match entry {
+        FallbackEntry::External(external) => {
+            let matching = external.matching_codepoints(codepoints);
+            (!matching.is_empty()).then(|| external.url_for(&matching))
+        }
+        FallbackEntry::Local(_) => None,
+    })
+}
+
+/// 生成字体时依次尝试的子集来源
+enum SubsetCandidate {
+    /// 单个字体文件，使用其中包含的那部分请求码点
+    Single(String, Arc<FontProcessor>),
+    /// 主字体与替换字体合并，`parts` 同 `merge_subsets`
+    Merged(String, Vec<(String, Arc<FontProcessor>, Vec<u32>)>),
+}
+
+/// 用字体中包含的那部分码点生成WOFF2，没有可用字符或生成失败时返回 `None`
+async fn subset_available(
+    key: &str,
+    processor: &FontProcessor,
+    codepoints: &[u32],
+    preserve_hints: bool,
+    retries: u8,
+) -> Option<Vec<u8>> {
+    let available_chars = processor.get_available_chars(codepoints);
+    if available_chars.is_empty() {
+        return None;
+    }
+    
+    // 子集化使用独立的副本，并发请求之间不共享HarfBuzz实例
+    with_retries(key, retries, || {
+        let processor = processor.clone();
+        let woff2_data = if preserve_hints {
+            processor.generate_woff2(&available_chars)
+        } else {
+            processor
+                .subset_with_hints(&available_chars, false)
+                .and_then(|ttf_data| FontProcessor::ttf_to_woff2(&ttf_data))
+        };
+        woff2_data.map_err(|e| AppError::FontProcessingError(e.to_string()))
+    })
+    .await
+    .map_err(|e| log::warn!("生成WOFF2失败 {}: {}", key, e))
+    .ok()
+}
+
+/// 生成失败时按指数退避重试，全部失败时返回第一次的错误
+///
+/// 无法合并的字体组合每次结果都相同，不重试
+async fn with_retries<T>(
+    key: &str,
+    retries: u8,
+    mut generate: impl FnMut() -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let first_error = match generate() {
+        Ok(data) => return Ok(data),
+        Err(e @ AppError::MergeUnsupported(_)) => return Err(e),
+        Err(e) => e,
+    };
+    
+    for attempt in 1..=retries {
+        // 指数退避：100ms、200ms、400ms
+        let delay = Duration::from_millis(100 << (attempt - 1));
+        log::warn!("生成WOFF2失败 {}，{:?}后第 {} 次重试", key, delay, attempt);
+        tokio::time::sleep(delay).await;
+        if let Ok(data) = generate() {
+            return Ok(data);
+        }
+    }
+    Err(first_error)
+}
+
+/// 生成由多个字体文件的字形组成的TTF，`parts` 为字体ID、字体处理器和分配给它的码点
+///
+/// 第一个可以追加字形的字体文件生成基础子集，其余文件的字形追加到子集末尾。
+/// 都是CFF轮廓或可变字体时无法合并，返回 `MergeUnsupported` 而不是只含部分字符的字体
+fn merge_subsets(parts: &[(String, Arc<FontProcessor>, Vec<u32>)], preserve_hints: bool) -> Result<Vec<u8>, AppError> {
+    let base_index = match parts {
+        [_] => 0,
+        _ => parts.iter().position(|(_, processor, _)| processor.can_append_glyphs()).ok_or_else(|| {
+            let mut font_ids: Vec<&str> = parts.iter().map(|(font_id, _, _)| font_id.as_str()).collect();
+            font_ids.dedup();
+            AppError::MergeUnsupported(font_ids.join(","))
+        })?,
+    };
+    let (_, base, base_codepoints) = &parts[base_index];
+    let base_ttf = base
+        .subset_with_hints(base_codepoints, preserve_hints)
+        .map_err(|e| AppError::FontProcessingError(e.to_string()))?;
+    
+    let sources: Vec<(&[u8], &[u32])> = parts
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != base_index)
+        .map(|(_, (_, processor, codepoints))| (processor.font_data(), codepoints.as_slice()))
+        .collect();
+    if sources.is_empty() {
+        return Ok(base_ttf);
+    }
+    append_glyphs(&base_ttf, &sources).map_err(|e| AppError::FontProcessingError(e.to_string()))
+}
+
+/// 读取字体缓存目录中每个WOFF2子集的码点
+fn cached_codepoint_sets(font_dir: &Path) -> Vec<Vec<u32>> {
+    let mut codepoint_sets = Vec::new();
+    for entry in WalkDir::new(font_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let Ok(relative) = entry.path().strip_prefix(font_dir) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        match parse_cache_filename(&relative) {
+            Some(codepoints) => codepoint_sets.push(codepoints),
+            None => log::debug!("跳过无法识别的缓存文件: {:?}", entry.path()),
+        }
+    }
+    codepoint_sets
+}
+
+/// 把TTF数据编码为缓存文件内容
+#[cfg(feature = "compress-ttf-cache")]
+fn encode_ttf_cache(ttf_data: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Write;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(ttf_data)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "compress-ttf-cache"))]
+fn encode_ttf_cache(ttf_data: &[u8]) -> std::io::Result<Vec<u8>> {
+    Ok(ttf_data.to_vec())
+}
+
+/// 从缓存文件内容还原TTF数据
+#[cfg(feature = "compress-ttf-cache")]
+fn decode_ttf_cache(stored: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut ttf_data = Vec::new();
+    flate2::read::GzDecoder::new(stored).read_to_end(&mut ttf_data)?;
+    Ok(ttf_data)
+}
+
+#[cfg(not(feature = "compress-ttf-cache"))]
+fn decode_ttf_cache(stored: &[u8]) -> std::io::Result<Vec<u8>> {
+    Ok(stored.to_vec())
+}
+
+/// 以内存中的文本为基础合并重新读取的文本
+fn merge_localized(current: &Option<LocalizedText>, loaded: Option<LocalizedText>) -> Option<LocalizedText> {
+    match (current, loaded) {
+        (Some(current), Some(loaded)) => Some(current.merge(&loaded)),
+        (current, None) => current.clone(),
+        (None, loaded) => loaded,
+    }
+}
+
+/// 终端中的显示宽度，中日韩字符占两列
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c >= '\u{2E80}' { 2 } else { 1 }).sum()
+}
+
+/// 两个路径是否已经指向同一个文件
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// 读取缓存文件扩展属性中的字体ID
+#[cfg(target_os = "linux")]
+fn cache_xattr_font_id(path: &Path) -> Option<String> {
+    let value = xattr::get(path, XATTR_FONT_ID).ok()??;
+    String::from_utf8(value).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cache_xattr_font_id(_path: &Path) -> Option<String> {
+    None
+}
+
+/// 把缓存目录中扩展属性记录为旧字体ID的文件改为新ID
+#[cfg(target_os = "linux")]
+fn rewrite_cache_xattr_font_id(cache_dir: &Path, old_id: &str, new_id: &str) {
+    for entry in WalkDir::new(cache_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        if cache_xattr_font_id(entry.path()).as_deref() != Some(old_id) {
+            continue;
+        }
+        if let Err(e) = xattr::set(entry.path(), XATTR_FONT_ID, new_id.as_bytes()) {
+            log::warn!("更新扩展属性失败 {:?}: {}", entry.path(), e);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rewrite_cache_xattr_font_id(_cache_dir: &Path, _old_id: &str, _new_id: &str) {}
+
+/// 读取已有的缓存文件及其修改时间，不存在或读取失败时返回 `None`
+async fn read_cached_font(cache_path: &Path) -> Option<(Vec<u8>, Option<SystemTime>)> {
+    if !cache_path.exists() {
+        return None;
+    }
+    match tokio::fs::read(cache_path).await {
+        Ok(data) => {
+            log::debug!("使用缓存文件: {:?}", cache_path);
+            let modified = file_modified(cache_path).await;
+            Some((data, modified))
+        }
+        Err(e) => {
+            log::warn!("读取缓存文件失败 {:?}: {}", cache_path, e);
+            None
+        }
+    }
+}
+
+/// 列出静态目录中的所有缓存子集，按路径排序
+fn list_cache_entries(static_dir: &Path) -> Vec<CacheManifestEntry> {
+    let mut entries = Vec::new();
+    
+    for entry in WalkDir::new(static_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "woff2"))
+    {
+        let Ok(relative) = entry.path().strip_prefix(static_dir) else {
+            continue;
+        };
+        let components: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let font_id = cache_xattr_font_id(entry.path()).unwrap_or_else(|| components[0].clone());
+        
+        entries.push(CacheManifestEntry {
+            font_id,
+            path: components.join("/"),
+            size,
         });
     }
-}
\ No newline at end of file
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// 获取文件的修改时间
+async fn file_modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// 异步列出目录下的所有子目录，不跟随符号链接，目录无法读取时返回空列表
+async fn list_subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("读取目录失败 {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    
+    let mut dirs = Vec::new();
+    loop {
+        match entries.next_entry().await {
+            Ok(Some(entry)) => {
+                if entry.file_type().await.is_ok_and(|file_type| file_type.is_dir()) {
+                    dirs.push(entry.path());
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("读取目录项失败 {}: {}", dir.display(), e);
+                break;
+            }
+        }
+    }
+    dirs
+}
+
+/// 解压字体压缩包，只提取 `目录/config.json` 和 `目录/字体文件`，已存在的目录不会被覆盖
+///
+/// 解压后的总大小超过 `max_bytes` 时删除已解压的目录并返回错误
+fn extract_font_archive(data: &[u8], fonts_dir: &Path, max_bytes: u64) -> Result<(Vec<PathBuf>, Vec<BatchFailure>)> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    
+    // 按顶层目录分组
+    let mut entries: BTreeMap<String, Vec<(usize, String)>> = BTreeMap::new();
+    let mut declared_size: u64 = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let components: Vec<String> = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let [dir, name] = components.as_slice() else {
+            continue;
+        };
+        if dir.starts_with("__") || dir.starts_with('.') || name.starts_with('.') {
+            continue;
+        }
+        if name == "config.json" || is_font_file(name) {
+            declared_size = declared_size.saturating_add(file.size());
+            entries
+                .entry(dir.clone())
+                .or_default()
+                .push((index, name.clone()));
+        }
+    }
+    // 先按声明的大小拒绝，声明的大小不可信，解压时再按实际写入的字节数检查
+    if declared_size > max_bytes {
+        return Err(anyhow::anyhow!("解压后大小 {} 字节超过上限 {} 字节", declared_size, max_bytes));
+    }
+    
+    let mut extracted = Vec::new();
+    let mut failed = Vec::new();
+    let mut remaining = max_bytes;
+    for (dir, files) in entries {
+        let target_dir = fonts_dir.join(&dir);
+        if target_dir.exists() {
+            failed.push(BatchFailure {
+                name: dir,
+                error: "字体目录已存在".to_string(),
+            });
+            continue;
+        }
+        
+        extracted.push(target_dir.clone());
+        if let Err(e) = extract_font_dir(&mut archive, &target_dir, &files, &mut remaining) {
+            for dir in &extracted {
+                if let Err(e) = std::fs::remove_dir_all(dir) {
+                    log::warn!("删除字体目录失败 {}: {}", dir.display(), e);
+                }
+            }
+            return Err(e);
+        }
+    }
+    
+    Ok((extracted, failed))
+}
+
+/// 解压一个字体目录中的文件，`remaining` 为剩余可写入的字节数
+fn extract_font_dir(
+    archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    target_dir: &Path,
+    files: &[(usize, String)],
+    remaining: &mut u64,
+) -> Result<()> {
+    std::fs::create_dir_all(target_dir)?;
+    for (index, name) in files {
+        let file = archive.by_index(*index)?;
+        let mut output = std::fs::File::create(target_dir.join(name))?;
+        // 多读一个字节以区分恰好达到上限和超出上限
+        let written = std::io::copy(&mut std::io::Read::take(file, *remaining + 1), &mut output)?;
+        if written > *remaining {
+            return Err(anyhow::anyhow!("解压后大小超过上限"));
+        }
+        *remaining -= written;
+    }
+    Ok(())
+}
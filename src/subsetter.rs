@@ -0,0 +1,179 @@
+use crate::{error::AppError, service::FontService};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+/// 任务队列容量
+const QUEUE_CAPACITY: usize = 1024;
+
+/// 最多保留的任务数量，超出后丢弃最早完成的任务
+const MAX_RETAINED_JOBS: usize = 1000;
+
+/// 后台子集化任务的状态
+#[derive(Debug, Clone)]
+pub enum JobStatus {
+    Queued,
+    Processing,
+    Done(Vec<u8>),
+    Failed(String),
+}
+
+impl JobStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Processing => "processing",
+            JobStatus::Done(_) => "done",
+            JobStatus::Failed(_) => "failed",
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done(_) | JobStatus::Failed(_))
+    }
+}
+
+struct SubsetJob {
+    id: String,
+    font_id: Option<String>,
+    codepoints: Vec<u32>,
+}
+
+#[derive(Default)]
+struct JobTable {
+    statuses: HashMap<String, JobStatus>,
+    order: VecDeque<String>,
+}
+
+/// 后台子集化队列，提交任务后立即返回任务ID，由客户端轮询结果
+pub struct BackgroundSubsetter {
+    sender: mpsc::Sender<SubsetJob>,
+    receiver: Mutex<Option<mpsc::Receiver<SubsetJob>>>,
+    jobs: RwLock<JobTable>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Default for BackgroundSubsetter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackgroundSubsetter {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        Self {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            jobs: RwLock::new(JobTable::default()),
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// 启动后台工作任务，重复调用不会启动多个任务
+    pub fn start(service: &Arc<FontService>) {
+        let subsetter = service.subsetter();
+        let Some(mut receiver) = subsetter.receiver.lock().unwrap().take() else {
+            return;
+        };
+
+        let service = service.clone();
+        let handle = tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                // 缓存重建任务不在任务表中，更新状态时会被忽略
+                let subsetter = service.subsetter();
+                subsetter.set_status(&job.id, JobStatus::Processing).await;
+
+                let status = match service
+                    .regenerate_subset(job.font_id.as_deref(), &job.codepoints)
+                    .await
+                {
+                    Ok(woff2_data) => JobStatus::Done(woff2_data),
+                    Err(e) => {
+                        log::warn!("后台子集化任务失败 {}: {}", job.id, e);
+                        JobStatus::Failed(e.to_string())
+                    }
+                };
+                subsetter.set_status(&job.id, status).await;
+            }
+        });
+
+        *subsetter.worker.lock().unwrap() = Some(handle);
+    }
+
+    /// 停止后台工作任务
+    pub fn stop(&self) {
+        if let Some(handle) = self.worker.lock().unwrap().take() {
+            handle.abort();
+            log::info!("已停止后台子集化任务");
+        }
+    }
+
+    /// 提交子集化任务，返回任务ID
+    pub async fn submit(&self, font_id: Option<String>, codepoints: Vec<u32>) -> Result<String, AppError> {
+        let id = Uuid::new_v4().to_string();
+
+        {
+            let mut jobs = self.jobs.write().await;
+            jobs.statuses.insert(id.clone(), JobStatus::Queued);
+            jobs.order.push_back(id.clone());
+            Self::evict_finished(&mut jobs);
+        }
+
+        let job = SubsetJob {
+            id: id.clone(),
+            font_id,
+            codepoints,
+        };
+        if self.sender.try_send(job).is_err() {
+            self.jobs.write().await.statuses.remove(&id);
+            return Err(AppError::QueueFull);
+        }
+
+        log::info!("提交后台子集化任务: {}", id);
+        Ok(id)
+    }
+
+    /// 提交缓存重建任务，队列已满时等待空位，任务不记录状态，生成的数据不保留
+    ///
+    /// 后台任务已停止时返回 `QueueFull`
+    pub async fn submit_rebuild(&self, font_id: String, codepoints: Vec<u32>) -> Result<(), AppError> {
+        let job = SubsetJob {
+            id: Uuid::new_v4().to_string(),
+            font_id: Some(font_id),
+            codepoints,
+        };
+        self.sender.send(job).await.map_err(|_| AppError::QueueFull)
+    }
+
+    /// 查询任务状态
+    pub async fn status(&self, job_id: &str) -> Option<JobStatus> {
+        self.jobs.read().await.statuses.get(job_id).cloned()
+    }
+
+    async fn set_status(&self, job_id: &str, status: JobStatus) {
+        if let Some(current) = self.jobs.write().await.statuses.get_mut(job_id) {
+            *current = status;
+        }
+    }
+
+    /// 丢弃超出保留数量的最早完成的任务
+    fn evict_finished(jobs: &mut JobTable) {
+        while jobs.order.len() > MAX_RETAINED_JOBS {
+            let Some(index) = jobs.order.iter().position(|id| {
+                jobs.statuses.get(id).is_none_or(JobStatus::is_finished)
+            }) else {
+                break;
+            };
+            if let Some(id) = jobs.order.remove(index) {
+                jobs.statuses.remove(&id);
+            }
+        }
+    }
+}
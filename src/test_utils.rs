@@ -0,0 +1,383 @@
+use crate::{
+    backend::FontBackend,
+    config::{AppConfig, FontConfig},
+    error::AppError,
+    font::{FontMetrics, HintingType, KernPair, LigatureEntry, NameEntry, Os2Metadata, TableEntry, VariableAxis, Woff2Header},
+    render::encode_png,
+    service::{CacheCompactReport, CacheManifestEntry, CachedFont, CmapPage, CoverageRow, EmojiCoverage, FontBatchSummary, FontFileInfo, FontInfo, FontVerifyReport, GlyphPage},
+    subsetter::BackgroundSubsetter,
+    utils::{content_digest, generate_file_hash},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+/// 不读取磁盘的假WOFF2数据，只有文件头魔数是真实的
+pub const MOCK_WOFF2: [u8; 10] = *b"wOF2\0\0\0\0\0\0";
+
+/// 假TTF数据，只有sfnt版本号是真实的
+pub const MOCK_TTF: [u8; 4] = [0, 1, 0, 0];
+
+/// 返回固定结果的字体服务，供处理器测试使用
+pub struct MockFontService {
+    config: AppConfig,
+    subsetter: BackgroundSubsetter,
+    fonts: Vec<FontInfo>,
+    /// 通过 `record_request` 记录的码点
+    pub recorded: Mutex<Vec<u32>>,
+    /// 通过 `record_font_served` 记录的字体ID
+    pub served: Mutex<Vec<String>>,
+}
+
+impl MockFontService {
+    /// 创建包含指定字体ID的服务，每个字体对任意码点都返回 `MOCK_WOFF2`
+    pub fn new(font_ids: &[&str]) -> Self {
+        let fonts = font_ids
+            .iter()
+            .map(|id| FontInfo {
+                id: id.to_string(),
+                version: "1.0".to_string(),
+                font_family: id.to_string(),
+                license: String::new(),
+                fallback: Vec::new(),
+                name: None,
+                title: None,
+                description: None,
+                display_name: None,
+                source_url: None,
+                annotations: HashMap::new(),
+                glyph_count: 0,
+                color_emoji: false,
+                axes: Vec::new(),
+                checksum_verified: true,
+                variants: Vec::new(),
+            })
+            .collect();
+    
+        Self {
+            config: AppConfig::default(),
+            subsetter: BackgroundSubsetter::new(),
+            fonts,
+            recorded: Mutex::new(Vec::new()),
+            served: Mutex::new(Vec::new()),
+        }
+    }
+    
+    /// 替换配置，用于测试最少码点数量等限制
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = config;
+        self
+    }
+    
+    /// 修改字体的字体族名，默认与字体ID相同
+    pub fn with_font_family(mut self, font_id: &str, font_family: &str) -> Self {
+        if let Some(font) = self.fonts.iter_mut().find(|font| font.id == font_id) {
+            font.font_family = font_family.to_string();
+        }
+        self
+    }
+    
+    fn check_font(&self, font_id: &str) -> Result<(), AppError> {
+        if self.fonts.iter().any(|font| font.id == font_id) {
+            Ok(())
+        } else {
+            Err(AppError::FontNotFound(font_id.to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl FontBackend for MockFontService {
+    fn config(&self) -> &AppConfig {
+        &self.config
+    }
+    
+    fn subsetter(&self) -> &BackgroundSubsetter {
+        &self.subsetter
+    }
+    
+    fn record_request(&self, codepoints: &[u32]) {
+        self.recorded.lock().unwrap().extend_from_slice(codepoints);
+    }
+    
+    fn record_font_served(&self, font_id: &str) {
+        self.served.lock().unwrap().push(font_id.to_string());
+    }
+    
+    fn most_used_fonts(&self, top_n: usize) -> Vec<(String, u64)> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for font_id in self.served.lock().unwrap().iter() {
+            *counts.entry(font_id.clone()).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(top_n);
+        counts
+    }
+    
+    fn validate_font_config(&self, font_config: &FontConfig) -> Vec<String> {
+        font_config.validate()
+    }
+    
+    async fn list_fonts(&self) -> Vec<FontInfo> {
+        self.fonts.clone()
+    }
+    
+    async fn fonts_covering_codepoint(&self, _codepoint: u32) -> Vec<FontInfo> {
+        self.fonts.clone()
+    }
+    
+    /// 与 `detect_tofu` 相同，私用区的码点视为缺字
+    async fn least_covered_for(&self, codepoints: &[u32]) -> Vec<(String, f64)> {
+        let tofu = self.detect_tofu("", codepoints).await;
+        let coverage = (codepoints.len() - tofu.len()) as f64 * 100.0 / codepoints.len() as f64;
+        self.fonts.iter().map(|font| (font.id.clone(), coverage)).collect()
+    }
+    
+    async fn font_source_url(&self, font_id: &str) -> Result<Option<String>, AppError> {
+        self.check_font(font_id)?;
+        Ok(None)
+    }
+    
+    async fn font_annotations(&self, font_id: &str) -> Result<HashMap<String, String>, AppError> {
+        self.check_font(font_id)?;
+        Ok(HashMap::new())
+    }
+    
+    async fn update_annotations(
+        &self,
+        font_id: &str,
+        patch: HashMap<String, Option<String>>,
+    ) -> Result<HashMap<String, String>, AppError> {
+        self.check_font(font_id)?;
+        Ok(patch.into_iter().filter_map(|(key, value)| Some((key, value?))).collect())
+    }
+    
+    async fn rename_font(&self, old_id: &str, new_id: &str) -> Result<(), AppError> {
+        self.check_font(old_id)?;
+        if self.check_font(new_id).is_ok() {
+            return Err(AppError::FontExists(new_id.to_string()));
+        }
+        Ok(())
+    }
+    
+    async fn name_table(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<NameEntry>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn os2_metadata(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<Os2Metadata>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn cmap(&self, font_id: &str, _page: usize, _per_page: usize) -> Result<Vec<FontFileInfo<CmapPage>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn hinting_types(&self, font_id: &str) -> Result<Vec<FontFileInfo<HintingType>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn cff_private_dicts(&self, font_id: &str) -> Result<Vec<FontFileInfo<Option<HashMap<String, serde_json::Value>>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn variable_axes(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<VariableAxis>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn kern_tables(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<KernPair>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn table_entries(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<TableEntry>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn ligatures(&self, font_id: &str) -> Result<Vec<FontFileInfo<Vec<LigatureEntry>>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn metrics(&self, font_id: &str) -> Result<Vec<FontFileInfo<FontMetrics>>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn coverage_heatmap(&self, font_id: &str) -> Result<Vec<CoverageRow>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn font_ranges(&self, font_id: &str) -> Result<Vec<String>, AppError> {
+        self.check_font(font_id)?;
+        Ok(Vec::new())
+    }
+    
+    async fn emoji_coverage(&self, font_id: &str) -> Result<EmojiCoverage, AppError> {
+        self.check_font(font_id)?;
+        Ok(EmojiCoverage {
+            covered: 0,
+            total: 0,
+            codepoints: Vec::new(),
+        })
+    }
+    
+    async fn list_glyphs(&self, font_id: &str, page: usize, _per_page: usize) -> Result<GlyphPage, AppError> {
+        self.check_font(font_id)?;
+        Ok(GlyphPage {
+            codepoints: Vec::new(),
+            page,
+            total: 0,
+        })
+    }
+    
+    /// 辅助平面的码点由列表中另一个字体提供，便于测试合并回退字体
+    async fn fallback_sources(&self, font_id: &str, codepoints: &[u32]) -> Result<Vec<(String, Vec<u32>)>, AppError> {
+        self.check_font(font_id)?;
+        let Some(other) = self.fonts.iter().find(|font| font.id != font_id) else {
+            return Ok(vec![(font_id.to_string(), codepoints.to_vec())]);
+        };
+        let (basic, supplementary): (Vec<u32>, Vec<u32>) = codepoints.iter().partition(|&&cp| cp <= 0xFFFF);
+        let sources = [(font_id.to_string(), basic), (other.id.clone(), supplementary)];
+        Ok(sources.into_iter().filter(|(_, codepoints)| !codepoints.is_empty()).collect())
+    }
+    
+    /// 私用区（U+E000-F8FF）的码点视为缺字，便于测试
+    async fn detect_tofu(&self, _font_id: &str, codepoints: &[u32]) -> Vec<u32> {
+        codepoints.iter().copied().filter(|cp| (0xE000..=0xF8FF).contains(cp)).collect()
+    }
+    
+    async fn get_merged_font(&self, sources: &[(String, Vec<u32>)], ttf: bool, _preserve_hints: bool) -> Result<CachedFont, AppError> {
+        for (font_id, _) in sources {
+            self.check_font(font_id)?;
+        }
+        let data = if ttf { MOCK_TTF.to_vec() } else { MOCK_WOFF2.to_vec() };
+        Ok(CachedFont {
+            hash: generate_file_hash(&data),
+            content_digest: content_digest(&data),
+            data,
+            modified: None,
+            glyph_count: None,
+        })
+    }
+    
+    async fn get_cached_font(&self, font_id: &str, _codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        self.check_font(font_id)?;
+        Ok(CachedFont {
+            data: MOCK_WOFF2.to_vec(),
+            modified: None,
+            hash: generate_file_hash(&MOCK_WOFF2),
+            content_digest: content_digest(&MOCK_WOFF2),
+            glyph_count: None,
+        })
+    }
+    
+    async fn get_cached_ttf(&self, font_id: &str, _codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        self.check_font(font_id)?;
+        Ok(CachedFont {
+            data: MOCK_TTF.to_vec(),
+            modified: None,
+            hash: generate_file_hash(&MOCK_TTF),
+            content_digest: content_digest(&MOCK_TTF),
+            glyph_count: None,
+        })
+    }
+    
+    async fn get_cached_unhinted_font(&self, font_id: &str, _codepoints: &[u32]) -> Result<CachedFont, AppError> {
+        self.check_font(font_id)?;
+        Ok(CachedFont {
+            data: MOCK_WOFF2.to_vec(),
+            modified: None,
+            hash: generate_file_hash(&MOCK_WOFF2),
+            content_digest: content_digest(&MOCK_WOFF2),
+            glyph_count: None,
+        })
+    }
+    
+    /// 只有 `MOCK_WOFF2` 的哈希能找到子集
+    async fn get_subset_by_hash(&self, font_id: &str, hash: &str) -> Result<CachedFont, AppError> {
+        self.check_font(font_id)?;
+        if hash != generate_file_hash(&MOCK_WOFF2) {
+            return Err(AppError::CacheNotFound(hash.to_string()));
+        }
+        Ok(CachedFont {
+            data: MOCK_WOFF2.to_vec(),
+            modified: None,
+            hash: generate_file_hash(&MOCK_WOFF2),
+            content_digest: content_digest(&MOCK_WOFF2),
+            glyph_count: None,
+        })
+    }
+    
+    async fn woff2_header(&self, font_id: &str, _codepoints: &[u32]) -> Result<Woff2Header, AppError> {
+        self.check_font(font_id)?;
+        Err(AppError::CacheNotFound(font_id.to_string()))
+    }
+    
+    async fn estimate_subset_size(&self, font_id: &str, codepoints: &[u32]) -> Result<usize, AppError> {
+        self.check_font(font_id)?;
+        Ok(codepoints.len() * MOCK_WOFF2.len())
+    }
+    
+    async fn glyph_png(&self, font_id: &str, _codepoint: u32, size: u32) -> Result<Vec<u8>, AppError> {
+        self.check_font(font_id)?;
+        let blank = vec![0; (size * size) as usize];
+        Ok(encode_png(&blank, size)?)
+    }
+    
+    async fn regenerate_font(&self, font_id: Option<&str>, _codepoints: &[u32]) -> Result<(), AppError> {
+        match font_id {
+            Some(id) => self.check_font(id),
+            None => Ok(()),
+        }
+    }
+    
+    async fn rebuild_cache(&self, font_id: &str) -> Result<usize, AppError> {
+        self.check_font(font_id)?;
+        Ok(0)
+    }
+    
+    async fn install_font_archive(&self, _data: Vec<u8>) -> Result<FontBatchSummary, AppError> {
+        Ok(FontBatchSummary {
+            loaded: 0,
+            fonts: Vec::new(),
+            failed: Vec::new(),
+        })
+    }
+    
+    fn cache_entries(&self) -> Vec<CacheManifestEntry> {
+        Vec::new()
+    }
+    
+    async fn count_subsets(&self, font_id: &str) -> Result<usize, AppError> {
+        self.check_font(font_id)?;
+        Ok(0)
+    }
+    
+    async fn export_cache_manifest(&self, _output_path: &Path) -> Result<()> {
+        Ok(())
+    }
+    
+    async fn verify_all_fonts(&self) -> Result<FontVerifyReport, AppError> {
+        Ok(FontVerifyReport {
+            ok: self.fonts.iter().map(|font| font.id.clone()).collect(),
+            failed: Vec::new(),
+        })
+    }
+    
+    async fn compact_cache(&self) -> Result<CacheCompactReport, AppError> {
+        Ok(CacheCompactReport {
+            files: 0,
+            linked: 0,
+            saved_bytes: 0,
+        })
+    }
+}
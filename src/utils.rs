@@ -1,4 +1,12 @@
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use std::{
+    io::{Cursor, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+/// 单个范围表达式最多展开的码点数量
+const MAX_RANGE_CODEPOINTS: u32 = 0x10000;
 
 /// 解析逗号分隔的unicode码点字符串
 pub fn parse_codepoints(chars_str: &str) -> Result<Vec<u32>, std::num::ParseIntError> {
@@ -8,6 +16,221 @@ pub fn parse_codepoints(chars_str: &str) -> Result<Vec<u32>, std::num::ParseIntE
         .collect()
 }
 
+/// 解析 `[20013, 25991]` 形式的JSON数组，不是JSON数组时按逗号分隔的十进制码点解析
+pub fn parse_codepoints_from_json(input: &str) -> Result<Vec<u32>> {
+    if let Ok(codepoints) = serde_json::from_str::<Vec<u32>>(input) {
+        return Ok(codepoints);
+    }
+    Ok(parse_codepoints(input)?)
+}
+
+/// 解析字符列表文件，每行一个字符、十进制码点或 `U+XXXX`，忽略空行和 `#` 注释
+pub fn parse_charset_file(content: &str) -> Vec<u32> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            if let Ok(codepoint) = line.parse::<u32>() {
+                return Some(codepoint);
+            }
+            if line.starts_with("U+") || line.starts_with("u+") {
+                return parse_hex_codepoint(&line[2..]).ok();
+            }
+            let mut chars = line.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Some(ch as u32),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// 解析CSS `unicode-range` 风格的码点，如 `U+4E00-9FFF`、`U+4E2D`，可用逗号混合
+pub fn parse_codepoints_range(input: &str) -> Result<Vec<u32>> {
+    Ok(parse_unicode_ranges(input)?.into_iter().flatten().collect())
+}
+
+/// 把CSS `unicode-range` 风格的字符串解析为码点区间，不展开区间内的码点
+pub fn parse_unicode_ranges(input: &str) -> Result<Vec<RangeInclusive<u32>>> {
+    let mut ranges = Vec::new();
+    
+    for part in input.split(',') {
+        let part = part.trim();
+        let hex = part
+            .strip_prefix("U+")
+            .or_else(|| part.strip_prefix("u+"))
+            .ok_or_else(|| anyhow!("缺少 U+ 前缀: {}", part))?;
+        
+        let (start, end) = match hex.split_once('-') {
+            Some((start, end)) => {
+                let end = end
+                    .strip_prefix("U+")
+                    .or_else(|| end.strip_prefix("u+"))
+                    .unwrap_or(end);
+                (parse_hex_codepoint(start)?, parse_hex_codepoint(end)?)
+            }
+            None => {
+                let codepoint = parse_hex_codepoint(hex)?;
+                (codepoint, codepoint)
+            }
+        };
+        
+        if start > end {
+            return Err(anyhow!("范围起点大于终点: {}", part));
+        }
+        if end - start >= MAX_RANGE_CODEPOINTS {
+            return Err(anyhow!("范围过大: {}", part));
+        }
+        ranges.push(start..=end);
+    }
+    
+    Ok(ranges)
+}
+
+/// 解析没有分隔符的连续 `U+XXXX` 序列，如 `U+4E2DU+6587`
+pub fn parse_codepoints_dense(input: &str) -> Result<Vec<u32>> {
+    // 十六进制数字中没有 `u`，可以直接统一前缀大小写
+    let normalized = input.trim().replace("u+", "U+");
+    let rest = normalized
+        .strip_prefix("U+")
+        .ok_or_else(|| anyhow!("缺少 U+ 前缀: {}", input))?;
+    
+    rest.split("U+").map(parse_hex_codepoint).collect()
+}
+
+fn parse_hex_codepoint(hex: &str) -> Result<u32> {
+    let codepoint = u32::from_str_radix(hex.trim(), 16)?;
+    if codepoint > 0x10FFFF {
+        return Err(anyhow!("码点超出Unicode范围: {}", hex));
+    }
+    Ok(codepoint)
+}
+
+/// 将升序码点合并为CSS `unicode-range` 片段，如 `U+4E00-4E02`、`U+20`
+pub fn format_unicode_ranges(codepoints: &[u32]) -> Vec<String> {
+    let mut ranges = Vec::new();
+    let mut iter = codepoints.iter().copied();
+    let Some(first) = iter.next() else {
+        return ranges;
+    };
+    
+    let (mut start, mut end) = (first, first);
+    for cp in iter {
+        if cp == end + 1 {
+            end = cp;
+            continue;
+        }
+        ranges.push(format_unicode_range(start, end));
+        start = cp;
+        end = cp;
+    }
+    ranges.push(format_unicode_range(start, end));
+    ranges
+}
+
+fn format_unicode_range(start: u32, end: u32) -> String {
+    if start == end {
+        format!("U+{:X}", start)
+    } else {
+        format!("U+{:X}-{:X}", start, end)
+    }
+}
+
+/// CJK统一表意文字的各个区块，按Unicode收录顺序排列
+pub const HAN_IDEOGRAPH_BLOCKS: [&str; 10] = [
+    "CJK-Unified",
+    "CJK-Ext-A",
+    "CJK-Ext-B",
+    "CJK-Ext-C",
+    "CJK-Ext-D",
+    "CJK-Ext-E",
+    "CJK-Ext-F",
+    "CJK-Ext-G",
+    "CJK-Ext-H",
+    "CJK-Ext-I",
+];
+
+/// 包含emoji的Unicode区块，区块中也有不属于emoji的符号和未分配的码点
+pub const EMOJI_BLOCKS: [&str; 7] = [
+    "Misc-Symbols",
+    "Dingbats",
+    "Misc-Symbols-Pictographs",
+    "Emoticons",
+    "Transport-Map-Symbols",
+    "Supplemental-Symbols-Pictographs",
+    "Symbols-Pictographs-Ext-A",
+];
+
+/// 按名称查找Unicode区块的码点范围，用于 `overrides/` 中的替换字体
+pub fn unicode_block_range(name: &str) -> Option<RangeInclusive<u32>> {
+    let range = match name {
+        "Misc-Symbols" => 0x2600..=0x26FF,
+        "Dingbats" => 0x2700..=0x27BF,
+        "CJK-Radicals-Supplement" => 0x2E80..=0x2EFF,
+        "Kangxi-Radicals" => 0x2F00..=0x2FDF,
+        "CJK-Symbols-Punctuation" => 0x3000..=0x303F,
+        "CJK-Ext-A" => 0x3400..=0x4DBF,
+        "CJK-Unified" => 0x4E00..=0x9FFF,
+        "CJK-Compatibility" => 0xF900..=0xFAFF,
+        "Misc-Symbols-Pictographs" => 0x1F300..=0x1F5FF,
+        "Emoticons" => 0x1F600..=0x1F64F,
+        "Transport-Map-Symbols" => 0x1F680..=0x1F6FF,
+        "Supplemental-Symbols-Pictographs" => 0x1F900..=0x1F9FF,
+        "Symbols-Pictographs-Ext-A" => 0x1FA70..=0x1FAFF,
+        "CJK-Ext-B" => 0x20000..=0x2A6DF,
+        "CJK-Ext-C" => 0x2A700..=0x2B73F,
+        "CJK-Ext-D" => 0x2B740..=0x2B81F,
+        "CJK-Ext-E" => 0x2B820..=0x2CEAF,
+        "CJK-Ext-F" => 0x2CEB0..=0x2EBEF,
+        "CJK-Ext-I" => 0x2EBF0..=0x2EE5F,
+        "CJK-Compatibility-Supplement" => 0x2F800..=0x2FA1F,
+        "CJK-Ext-G" => 0x30000..=0x3134F,
+        "CJK-Ext-H" => 0x31350..=0x323AF,
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// 解析 `Accept-Language` 请求头，按权重从高到低返回小写的语言标签
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut languages: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.trim().split(';');
+            let tag = params.next()?.trim().to_lowercase();
+            if tag.is_empty() {
+                return None;
+            }
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    
+    // 稳定排序，权重相同时保持原有顺序
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// 转义HTML特殊字符
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
 /// 生成缓存文件名
 pub fn generate_cache_filename(codepoints: &[u32]) -> String {
     let mut sorted_codepoints = codepoints.to_vec();
@@ -25,9 +248,89 @@ pub fn generate_cache_filename(codepoints: &[u32]) -> String {
     }
 }
 
-/// 生成文件的MD5哈希
+/// TTF缓存文件的扩展名，启用 `compress-ttf-cache` 时以gzip压缩存储
+#[cfg(feature = "compress-ttf-cache")]
+pub const TTF_CACHE_EXTENSION: &str = "ttf.gz";
+#[cfg(not(feature = "compress-ttf-cache"))]
+pub const TTF_CACHE_EXTENSION: &str = "ttf";
+
+/// 生成TTF缓存文件相对于字体缓存目录的路径，统一放在 `ttf-cache/` 子目录中
+pub fn generate_ttf_cache_filename(codepoints: &[u32]) -> String {
+    let mut sorted_codepoints = codepoints.to_vec();
+    sorted_codepoints.sort_unstable();
+    let codepoints_str = sorted_codepoints
+        .iter()
+        .map(|cp| cp.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("ttf-cache/{}.{}", codepoints_str, TTF_CACHE_EXTENSION)
+}
+
+/// 生成去掉hinting的WOFF2缓存文件相对于字体缓存目录的路径，统一放在 `unhinted/` 子目录中
+pub fn generate_unhinted_cache_filename(codepoints: &[u32]) -> String {
+    let mut sorted_codepoints = codepoints.to_vec();
+    sorted_codepoints.sort_unstable();
+    let codepoints_str = sorted_codepoints
+        .iter()
+        .map(|cp| cp.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("unhinted/{}.woff2", codepoints_str)
+}
+
+/// 从 `generate_cache_filename` 生成的相对路径还原码点，无法识别时返回 `None`
+pub fn parse_cache_filename(relative_path: &str) -> Option<Vec<u32>> {
+    let name = relative_path.strip_prefix("cache/").unwrap_or(relative_path);
+    let stem = name.strip_suffix(".woff2")?;
+    let codepoints = parse_codepoints(stem).ok()?;
+    (!codepoints.is_empty()).then_some(codepoints)
+}
+
+/// 将多个文件打包为不压缩的ZIP，WOFF2本身已经压缩过
+pub fn build_zip(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored);
+    for (name, data) in entries {
+        writer.start_file(name.as_str(), options)?;
+        writer.write_all(data)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+/// 判断文件名是否为支持的字体文件
+pub fn is_font_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".ttf") || name.ends_with(".otf") || name.ends_with(".ttc")
+}
+
+/// 按日期生成滚动日志文件路径，例如 `access.log` -> `access.log.2024-01-31`
+pub fn dated_log_path(path: &Path, date: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(date);
+    path.with_file_name(name)
+}
+
+/// 生成文件的BLAKE3哈希（64个十六进制字符）
 pub fn generate_file_hash(data: &[u8]) -> String {
-    format!("{:x}", md5::compute(data))
+    blake3::hash(data).to_hex().to_string()
+}
+
+/// 生成数据的SHA-256哈希（小写十六进制）
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 生成RFC 9530 `Content-Digest` 响应头的值，如 `sha-256=:...:`
+pub fn content_digest(data: &[u8]) -> String {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use sha2::{Digest, Sha256};
+    format!("sha-256=:{}:", BASE64.encode(Sha256::digest(data)))
 }
 
 /// 检查文件是否过期
@@ -51,11 +354,9 @@ pub fn cleanup_expired_cache(cache_dir: &Path, days: u64) -> std::io::Result<usi
             let entry = entry?;
             let path = entry.path();
             
-            if path.is_file() && is_file_expired(&path, days) {
-                if std::fs::remove_file(&path).is_ok() {
-                    cleaned_count += 1;
-                    log::info!("清理过期缓存文件: {:?}", path);
-                }
+            if path.is_file() && is_file_expired(&path, days) && std::fs::remove_file(&path).is_ok() {
+                cleaned_count += 1;
+                log::info!("清理过期缓存文件: {:?}", path);
             }
         }
     }
@@ -74,9 +375,133 @@ mod tests {
         assert_eq!(parse_codepoints("40339, 40340, 40341").unwrap(), vec![40339, 40340, 40341]);
     }
 
+    #[test]
+    fn test_parse_codepoints_from_json() {
+        assert_eq!(parse_codepoints_from_json("[20013, 25991]").unwrap(), vec![20013, 25991]);
+        assert_eq!(parse_codepoints_from_json("[]").unwrap(), Vec::<u32>::new());
+        assert_eq!(parse_codepoints_from_json("20013,25991").unwrap(), vec![20013, 25991]);
+        assert!(parse_codepoints_from_json("[20013, -1]").is_err());
+        assert!(parse_codepoints_from_json("[\"中\"]").is_err());
+    }
+
+    #[test]
+    fn test_parse_codepoints_range() {
+        assert_eq!(parse_codepoints_range("U+4E2D").unwrap(), vec![0x4E2D]);
+        assert_eq!(parse_codepoints_range("U+4E00-4E02").unwrap(), vec![0x4E00, 0x4E01, 0x4E02]);
+        assert_eq!(parse_codepoints_range("u+4e00-U+4e01, U+20").unwrap(), vec![0x4E00, 0x4E01, 0x20]);
+        assert!(parse_codepoints_range("4E2D").is_err());
+        assert!(parse_codepoints_range("U+9FFF-4E00").is_err());
+        assert!(parse_codepoints_range("U+110000").is_err());
+        assert!(parse_codepoints_range("U+0-10FFFF").is_err());
+    }
+
+    #[test]
+    fn test_parse_codepoints_dense() {
+        assert_eq!(parse_codepoints_dense("U+4E2DU+6587").unwrap(), vec![0x4E2D, 0x6587]);
+        assert_eq!(parse_codepoints_dense("u+4e2dU+20").unwrap(), vec![0x4E2D, 0x20]);
+        assert!(parse_codepoints_dense("4E2DU+6587").is_err());
+        assert!(parse_codepoints_dense("U+4E2DU+").is_err());
+        assert!(parse_codepoints_dense("U+110000").is_err());
+    }
+
+    #[test]
+    fn test_parse_cache_filename() {
+        for codepoints in [vec![20013], vec![65, 20013, 25991]] {
+            let filename = generate_cache_filename(&codepoints);
+            assert_eq!(parse_cache_filename(&filename), Some(codepoints));
+        }
+        assert_eq!(parse_cache_filename("manifest.json"), None);
+        assert_eq!(parse_cache_filename("abc.woff2"), None);
+    }
+
+    #[test]
+    fn test_format_unicode_ranges() {
+        assert_eq!(
+            format_unicode_ranges(&[0x20, 0x4E00, 0x4E01, 0x4E02, 0x4E2D]),
+            vec!["U+20", "U+4E00-4E02", "U+4E2D"]
+        );
+        assert!(format_unicode_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_build_zip() {
+        let entries = vec![("a.woff2".to_string(), b"wOF2a".to_vec()), ("b.woff2".to_string(), Vec::new())];
+        let data = build_zip(&entries).unwrap();
+        
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name("a.woff2").unwrap(), &mut content).unwrap();
+        assert_eq!(content, b"wOF2a");
+    }
+
+    #[test]
+    fn test_unicode_block_range() {
+        assert!(unicode_block_range("CJK-Unified").unwrap().contains(&0x4E2D));
+        assert!(unicode_block_range("CJK-Ext-B").unwrap().contains(&0x20000));
+        assert_eq!(unicode_block_range("Latin"), None);
+    }
+
+    #[test]
+    fn test_parse_accept_language() {
+        assert_eq!(
+            parse_accept_language("en-US,en;q=0.5,zh-TW;q=0.8"),
+            vec!["en-us", "zh-tw", "en"]
+        );
+        assert_eq!(parse_accept_language("zh-Hant, *;q=0"), vec!["zh-hant"]);
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_charset_file() {
+        assert_eq!(
+            parse_charset_file("𣚦\n䓪\n\n# 注释\n20013\nU+4E2D\n无效行\n"),
+            vec![0x236A6, 0x44EA, 20013, 0x4E2D]
+        );
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("中文"), "中文");
+        assert_eq!(escape_html("<b>\"a\" & 'b'</b>"), "&lt;b&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_generate_file_hash() {
+        assert_eq!(
+            generate_file_hash(b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(generate_file_hash(b"webfont").len(), 64);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_content_digest() {
+        assert_eq!(
+            content_digest(b"abc"),
+            "sha-256=:ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=:"
+        );
+    }
+
     #[test]
     fn test_generate_cache_filename() {
         assert_eq!(generate_cache_filename(&[40339]), "40339.woff2");
         assert_eq!(generate_cache_filename(&[40341, 40339, 40340]), "cache/40339,40340,40341.woff2");
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_dated_log_path() {
+        assert_eq!(
+            dated_log_path(Path::new("logs/access.log"), "2024-01-31"),
+            PathBuf::from("logs/access.log.2024-01-31")
+        );
+    }
+}
@@ -0,0 +1,885 @@
+#![cfg(feature = "subsetting")]
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use webfont_zh::{
+    config::{AppConfig, FontConfig},
+    error::AppError,
+    font::{FontProcessor, HintingType, Woff2Header},
+    service::{CmapPage, FontService},
+    subsetter::BackgroundSubsetter,
+    utils::{build_zip, generate_cache_filename, generate_file_hash, generate_ttf_cache_filename, generate_unhinted_cache_filename},
+};
+
+/// 文津宋体基本平面，“中”字一定在其中
+const TEST_FONT: &str = "data/fonts/WenJinMincho/WenJinMinchoP0-Regular.ttf";
+const TEST_CODEPOINT: u32 = 0x4E2D;
+
+fn data_path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+/// 解压WOFF2并检查结果是只包含请求字符的有效字体
+fn assert_valid_woff2(woff2_data: &[u8], codepoint: u32) {
+    assert_eq!(&woff2_data[..4], b"wOF2");
+    let ttf_data = woff::version2::decompress(woff2_data).expect("WOFF2解压失败");
+    let face = ttf_parser::Face::parse(&ttf_data, 0).expect("子集字体无法解析");
+    let ch = char::from_u32(codepoint).unwrap();
+    assert!(face.glyph_index(ch).is_some());
+    assert!(face.glyph_index('A').is_none());
+}
+
+#[test]
+fn generate_woff2_produces_valid_font() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert!(processor.contains_char(TEST_CODEPOINT));
+    
+    let woff2_data = processor.generate_woff2(&[TEST_CODEPOINT]).unwrap();
+    assert_valid_woff2(&woff2_data, TEST_CODEPOINT);
+}
+
+#[test]
+fn truncated_font_is_rejected() {
+    let font_data = std::fs::read(data_path(TEST_FONT)).unwrap();
+    assert!(FontProcessor::from_bytes(font_data[..1 << 20].to_vec()).is_err());
+}
+
+#[test]
+fn shipped_font_has_no_validation_warnings() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert!(processor.validate().is_empty());
+}
+
+#[test]
+fn family_name_matches_ignoring_case() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert!(processor.family_name_matches("WenJin Mincho Plane 0"));
+    assert!(processor.family_name_matches("wenjin mincho plane 0"));
+    assert!(!processor.family_name_matches("Plangothic P1"));
+}
+
+#[test]
+fn woff2_header_matches_generated_file() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let woff2_data = processor.generate_woff2(&[TEST_CODEPOINT]).unwrap();
+    
+    let header = Woff2Header::parse(&woff2_data).unwrap();
+    assert_eq!(header.sf_version, "0x00010000");
+    assert_eq!(header.length as usize, woff2_data.len());
+    assert!(header.num_tables > 0);
+    assert!(Woff2Header::parse(&woff2_data[..Woff2Header::SIZE - 1]).is_err());
+    assert!(Woff2Header::parse(&[0u8; Woff2Header::SIZE]).is_err());
+}
+
+#[test]
+fn os2_metadata_is_read() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let metadata = processor.os2_metadata().expect("缺少OS/2表");
+    assert!(metadata.units_per_em > 0);
+    assert!(metadata.ascender > 0);
+    assert!((1..=1000).contains(&metadata.weight_class));
+}
+
+#[test]
+fn metrics_are_scaled_by_units_per_em() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let metrics = processor.metrics();
+    assert!(metrics.units_per_em > 0);
+    
+    let expected = f32::from(metrics.ascender.units) / f32::from(metrics.units_per_em);
+    assert!((metrics.ascender.em - expected).abs() < f32::EPSILON);
+    assert!(metrics.descender.units <= 0);
+}
+
+#[test]
+fn table_entries_list_required_tables() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let tables = processor.table_entries();
+    for tag in ["cmap", "glyf", "head"] {
+        let table = tables.iter().find(|t| t.tag == tag).expect(tag);
+        assert!(table.size > 0);
+    }
+}
+
+#[test]
+fn cmap_entries_map_to_glyphs() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let entries = processor.cmap_entries();
+    assert_eq!(entries.len(), processor.get_all_codepoints().len());
+    assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(entries.iter().all(|&(_, glyph_id)| glyph_id > 0 && glyph_id < processor.number_of_glyphs()));
+    
+    let page = CmapPage::paginate(entries, 1, 2);
+    let json = serde_json::to_value(&page).unwrap();
+    assert_eq!(json["entries"].as_object().unwrap().len(), 2);
+    assert!(json["total"].as_u64().unwrap() > 2);
+}
+
+#[test]
+fn ligatures_reference_existing_glyphs() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let ligatures = processor.ligatures();
+    assert!(!ligatures.is_empty());
+    
+    let glyph_count = processor.number_of_glyphs();
+    for entry in &ligatures {
+        assert!(!entry.sequence.is_empty());
+        assert!(entry.ligature < glyph_count);
+        assert!(entry.sequence.iter().all(|&glyph| glyph < glyph_count));
+    }
+}
+
+#[test]
+fn hinting_type_follows_outline_format() {
+    let cff = FontProcessor::new(&data_path("data/fonts/SourceHanSans/SourceHanSansSC.otf")).unwrap();
+    assert_eq!(cff.hinting_type(), HintingType::Cff);
+    
+    let unhinted = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert_eq!(unhinted.hinting_type(), HintingType::None);
+}
+
+#[test]
+fn cff_private_dict_is_read_for_cff_fonts() {
+    let cff = FontProcessor::new(&data_path("data/fonts/SourceHanSans/SourceHanSansSC.otf")).unwrap();
+    let dict = cff.cff_private_dict().unwrap();
+    // BlueValues成对出现，差值已还原为递增的绝对值
+    let blue_values: Vec<i64> = dict["BlueValues"].as_array().unwrap().iter().map(|v| v.as_i64().unwrap()).collect();
+    assert_eq!(blue_values.len() % 2, 0);
+    assert!(blue_values.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(dict["StdVW"].as_i64().unwrap() > 0);
+    
+    let truetype = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert!(truetype.cff_private_dict().is_none());
+}
+
+#[test]
+fn outline_fonts_have_no_color_glyphs() {
+    for path in [TEST_FONT, "data/fonts/SourceHanSans/SourceHanSansSC.otf"] {
+        let processor = FontProcessor::new(&data_path(path)).unwrap();
+        assert!(!processor.has_color_glyphs());
+    }
+}
+
+#[test]
+fn static_fonts_have_no_variable_axes() {
+    for path in [TEST_FONT, "data/fonts/SourceHanSans/SourceHanSansSC.otf"] {
+        let processor = FontProcessor::new(&data_path(path)).unwrap();
+        assert!(processor.variable_axes().is_empty());
+    }
+}
+
+#[test]
+fn fonts_without_kern_table_have_no_kern_pairs() {
+    // 内置字体只用GPOS表实现字偶距
+    for path in [TEST_FONT, "data/fonts/SourceHanSans/SourceHanSansSC.otf"] {
+        let processor = FontProcessor::new(&data_path(path)).unwrap();
+        assert!(processor.table_entries().iter().all(|table| table.tag != "kern"));
+        assert!(processor.full_kern_table().is_empty());
+    }
+}
+
+#[test]
+fn subset_glyph_count_reads_woff2_and_ttf() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let codepoints = [TEST_CODEPOINT, 0x6587];
+    
+    // 子集包含 .notdef 和两个请求的字形
+    let ttf_data = processor.subset_font(&codepoints).unwrap();
+    assert_eq!(FontProcessor::subset_glyph_count(&ttf_data), Some(3));
+    let woff2_data = processor.generate_woff2(&codepoints).unwrap();
+    assert_eq!(FontProcessor::subset_glyph_count(&woff2_data), Some(3));
+    
+    assert_eq!(FontProcessor::subset_glyph_count(b"wOF2\0\0\0\0"), None);
+}
+
+#[test]
+fn dropping_hints_shrinks_cff_subset() {
+    let processor = FontProcessor::new(&data_path("data/fonts/SourceHanSans/SourceHanSansSC.otf")).unwrap();
+    let codepoints: Vec<u32> = (0x4E00..0x4E40).collect();
+    
+    let hinted = processor.subset_with_hints(&codepoints, true).unwrap();
+    let unhinted = processor.subset_with_hints(&codepoints, false).unwrap();
+    assert!(unhinted.len() < hinted.len(), "{} >= {}", unhinted.len(), hinted.len());
+    assert_eq!(FontProcessor::subset_glyph_count(&unhinted), FontProcessor::subset_glyph_count(&hinted));
+}
+
+#[test]
+fn cloned_processor_subsets_independently() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let cloned = processor.clone();
+    assert_eq!(cloned.font_data().as_ptr(), processor.font_data().as_ptr());
+    
+    let woff2_data = std::thread::spawn(move || cloned.generate_woff2(&[TEST_CODEPOINT]).unwrap())
+        .join()
+        .unwrap();
+    assert_valid_woff2(&woff2_data, TEST_CODEPOINT);
+}
+
+#[test]
+fn font_data_is_released_with_last_processor() {
+    let font_data: Arc<[u8]> = std::fs::read(data_path(TEST_FONT)).unwrap().into();
+    let processor = FontProcessor::from_shared(font_data.clone()).unwrap();
+    let cloned = processor.clone();
+    assert!(Arc::strong_count(&font_data) > 1);
+    
+    drop(processor);
+    drop(cloned);
+    assert_eq!(Arc::strong_count(&font_data), 1);
+}
+
+#[test]
+fn glyph_png_renders_visible_pixels() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    let png = processor.glyph_png(TEST_CODEPOINT, 32).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 32);
+    
+    let svg = processor.glyph_svg(TEST_CODEPOINT, 32).unwrap();
+    assert!(svg.starts_with("<svg") && svg.contains(r#"d="M"#));
+    assert!(processor.glyph_svg(0x10FFFF, 32).is_none());
+    assert!(processor.glyph_png(0x10FFFF, 32).is_err());
+}
+
+#[tokio::test]
+async fn service_exposes_font_processor() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    assert!(service.get_font_processor("WenJinMincho", "missing").await.is_none());
+    
+    let processor = service
+        .get_font_processor("WenJinMincho", "WenJin Mincho Plane 0")
+        .await
+        .expect("未找到字体处理器");
+    let woff2_data = processor.generate_woff2(&[TEST_CODEPOINT]).unwrap();
+    assert_valid_woff2(&woff2_data, TEST_CODEPOINT);
+}
+
+#[tokio::test]
+async fn coverage_heatmap_covers_unified_block() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let rows = service.coverage_heatmap("WenJinMincho").await.unwrap();
+    let unified = rows.iter().find(|row| row.block == "CJK-Unified").unwrap();
+    assert_eq!(unified.start, "U+4E00");
+    assert_eq!(unified.cells.len(), 82);
+    assert_eq!(unified.cells[0], 100.0);
+    assert!(rows.iter().flat_map(|row| &row.cells).all(|&cell| (0.0..=100.0).contains(&cell)));
+}
+
+#[tokio::test]
+async fn emoji_coverage_stays_within_emoji_blocks() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let coverage = service.emoji_coverage("SourceHanSans").await.unwrap();
+    assert_eq!(coverage.total, 1824);
+    assert_eq!(coverage.covered, coverage.codepoints.len());
+    assert!(coverage
+        .codepoints
+        .iter()
+        .all(|cp| (0x2600..=0x27BF).contains(cp) || (0x1F300..=0x1FAFF).contains(cp)));
+}
+
+#[tokio::test]
+async fn validate_font_config_checks_font_files() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let mut font_config = FontConfig::load_from_dir(&data_path("data/fonts/WenJinMincho")).unwrap();
+    assert!(service.validate_font_config(&font_config).is_empty());
+    
+    font_config.files[0].path = "missing.ttf".to_string();
+    let errors = service.validate_font_config(&font_config);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0], "字体文件不存在: missing.ttf");
+    
+    // 字体目录外的路径只报告路径无效，不透露文件是否存在
+    let existing = data_path(TEST_FONT).display().to_string();
+    for path in [existing.as_str(), "../WenJinMincho/WenJinMinchoP0-Regular.ttf"] {
+        font_config.files[0].path = path.to_string();
+        let errors = service.validate_font_config(&font_config);
+        assert_eq!(errors, vec![format!("字体文件路径必须位于字体目录内: {}", path)]);
+    }
+}
+
+#[tokio::test]
+async fn invalid_scalar_values_are_rejected() {
+    let processor = FontProcessor::new(&data_path(TEST_FONT)).unwrap();
+    assert!(!processor.contains_char(0xD800));
+    assert_eq!(processor.get_available_chars(&[0xD800, 0x200000, TEST_CODEPOINT]), vec![TEST_CODEPOINT]);
+    
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 代理码点和超出U+10FFFF的值都不能生成字体
+    for codepoint in [0xD800, 0x200000] {
+        for font_id in [Some("WenJinMincho"), None] {
+            let result = service.generate_font(font_id, &[codepoint]).await;
+            assert!(matches!(result, Err(AppError::CharacterNotFound(cp)) if cp == codepoint));
+        }
+    }
+}
+
+#[tokio::test]
+async fn processor_for_codepoint_skips_fonts_without_glyph() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 思源黑体不包含扩展B区，按字体ID顺序也会跳过它
+    let (font_id, processor) = service.get_processor_for_codepoint(0x20000).await.unwrap();
+    assert!(processor.contains_char(0x20000));
+    assert!(!font_id.starts_with("SourceHanSans"));
+    assert!(service.get_processor_for_codepoint(0xD800).await.is_none());
+    
+    assert!(service.generate_font(None, &[0xD800, 0x20000]).await.is_ok());
+}
+
+#[tokio::test]
+async fn most_used_fonts_are_sorted_by_request_count() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    assert!(service.most_used_fonts(10).is_empty());
+    
+    for font_id in ["WenJinMincho", "SourceHanSans", "WenJinMincho", "Plangothic"] {
+        service.record_font_served(font_id);
+    }
+    assert_eq!(
+        service.most_used_fonts(2),
+        vec![("WenJinMincho".to_string(), 2), ("Plangothic".to_string(), 1)]
+    );
+}
+
+#[tokio::test]
+async fn least_covered_fonts_come_first() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 思源黑体不包含扩展B区，文津宋体的P2文件包含
+    let coverage = service.least_covered_for(&[TEST_CODEPOINT, 0x20000, TEST_CODEPOINT]).await;
+    assert_eq!(coverage.len(), service.list_fonts().await.len());
+    assert!(coverage[0].0.starts_with("SourceHanSans"));
+    assert_eq!(coverage[0].1, 50.0);
+    assert!(coverage.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    assert!(coverage.contains(&("WenJinMincho".to_string(), 100.0)));
+}
+
+#[tokio::test]
+async fn reload_font_replaces_processors() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let before = service
+        .get_font_processor("WenJinMincho", "WenJin Mincho Plane 0")
+        .await
+        .unwrap();
+    service.reload_font("WenJinMincho").await.unwrap();
+    let after = service
+        .get_font_processor("WenJinMincho", "WenJin Mincho Plane 0")
+        .await
+        .unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&before, &after));
+    assert!(service.get_font_processor("Plangothic", "Plangothic P1").await.is_some());
+    
+    assert!(service.reload_font("missing").await.is_err());
+}
+
+#[tokio::test]
+async fn fallback_sources_follow_fallback_order() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 遍黑体不含平假名“あ”，由第一个回退字体思源黑体提供
+    let sources = service.fallback_sources("Plangothic", &[0x3042, 0x20000]).await.unwrap();
+    assert_eq!(
+        sources,
+        vec![
+            ("Plangothic".to_string(), vec![0x20000]),
+            ("SourceHanSans".to_string(), vec![0x3042]),
+        ]
+    );
+    assert!(service.fallback_sources("missing", &[0x3042]).await.is_err());
+    
+    // U+10FFFF不在任何字体中
+    assert_eq!(service.detect_tofu("Plangothic", &[0x3042, 0x20000, 0x10FFFF]).await, vec![0x10FFFF]);
+}
+
+#[tokio::test]
+async fn fallback_depth_limit_stops_fallback_chain() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        max_fallback_depth: 0,
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 深度为0时不再查找回退字体，遍黑体本身不含平假名
+    let result = service.generate_font(Some("Plangothic"), &[0x3042]).await;
+    assert!(matches!(result, Err(AppError::CharacterNotFound(0x3042))));
+    assert!(service.generate_font(Some("Plangothic"), &[0x20000]).await.is_ok());
+}
+
+/// 字形在生成的WOFF2中的边界框
+fn glyph_bbox(woff2_data: &[u8], codepoint: u32) -> Option<ttf_parser::Rect> {
+    let ttf_data = FontProcessor::woff2_to_ttf(woff2_data).unwrap();
+    let face = ttf_parser::Face::parse(&ttf_data, 0).unwrap();
+    let glyph_id = face.glyph_index(char::from_u32(codepoint)?)?;
+    face.outline_glyph(glyph_id, &mut NoopOutline)
+}
+
+struct NoopOutline;
+
+impl ttf_parser::OutlineBuilder for NoopOutline {
+    fn move_to(&mut self, _x: f32, _y: f32) {}
+    fn line_to(&mut self, _x: f32, _y: f32) {}
+    fn quad_to(&mut self, _x1: f32, _y1: f32, _x: f32, _y: f32) {}
+    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, _x: f32, _y: f32) {}
+    fn close(&mut self) {}
+}
+
+#[tokio::test]
+async fn override_glyphs_are_merged_into_main_subset() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let font_dir = data_dir.path().join("fonts").join("mincho");
+    std::fs::create_dir_all(font_dir.join("overrides")).unwrap();
+    std::fs::copy(data_path(TEST_FONT), font_dir.join("mincho.ttf")).unwrap();
+    // 遍黑体P2中的“中”与文津宋体字形不同，它不含平假名“あ”
+    std::fs::copy(
+        data_path("data/fonts/plangothic/Plangothic P2.ttf"),
+        font_dir.join("overrides/CJK-Unified.ttf"),
+    )
+    .unwrap();
+    let mut font_config = FontConfig::detect_from_font_file(&font_dir.join("mincho.ttf")).unwrap();
+    font_config.overrides = vec!["CJK-Unified".to_string()];
+    font_config.save_to_dir(&font_dir).unwrap();
+    
+    let config = AppConfig {
+        data_dir: data_dir.path().to_path_buf(),
+        static_dir: data_dir.path().join("static"),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    let woff2_data = service.generate_font(Some("mincho"), &[TEST_CODEPOINT, 0x3042]).await.unwrap();
+    
+    let main = FontProcessor::new(&data_path(TEST_FONT)).unwrap().generate_woff2(&[TEST_CODEPOINT, 0x3042]).unwrap();
+    let replacement = FontProcessor::new(&data_path("data/fonts/plangothic/Plangothic P2.ttf"))
+        .unwrap()
+        .generate_woff2(&[TEST_CODEPOINT])
+        .unwrap();
+    // 区块内的字符使用替换字形，其余字符仍由主字体提供
+    assert_eq!(glyph_bbox(&woff2_data, TEST_CODEPOINT), glyph_bbox(&replacement, TEST_CODEPOINT));
+    assert_ne!(glyph_bbox(&woff2_data, TEST_CODEPOINT), glyph_bbox(&main, TEST_CODEPOINT));
+    assert!(glyph_bbox(&woff2_data, 0x3042).is_some());
+    assert_eq!(glyph_bbox(&woff2_data, 0x3042), glyph_bbox(&main, 0x3042));
+    
+    // 去掉hinting的子集同样使用替换字形
+    let unhinted = service.get_cached_unhinted_font("mincho", &[TEST_CODEPOINT, 0x3042]).await.unwrap();
+    assert_eq!(glyph_bbox(&unhinted.data, TEST_CODEPOINT), glyph_bbox(&replacement, TEST_CODEPOINT));
+    assert_eq!(glyph_bbox(&unhinted.data, 0x3042), glyph_bbox(&main, 0x3042));
+}
+
+#[tokio::test]
+async fn fallback_glyphs_are_merged_into_one_font() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    // 遍黑体的子集作为基础，思源黑体的CFF字形转换为TrueType轮廓后追加
+    let sources = service.fallback_sources("Plangothic", &[0x3042, 0x20000]).await.unwrap();
+    let merged = service.get_merged_font(&sources, false, true).await.unwrap();
+    assert_eq!(merged.glyph_count, Some(3));
+    let plangothic = FontProcessor::new(&data_path("data/fonts/plangothic/Plangothic P1.ttf"))
+        .unwrap()
+        .generate_woff2(&[0x20000])
+        .unwrap();
+    assert_eq!(glyph_bbox(&merged.data, 0x20000), glyph_bbox(&plangothic, 0x20000));
+    
+    let source_data = std::fs::read(data_path("data/fonts/SourceHanSans/SourceHanSansSC.otf")).unwrap();
+    let source = ttf_parser::Face::parse(&source_data, 0).unwrap();
+    let expected = source.outline_glyph(source.glyph_index('あ').unwrap(), &mut NoopOutline).unwrap();
+    let actual = glyph_bbox(&merged.data, 0x3042).unwrap();
+    // 三次曲线近似为二次曲线后，边界框最多相差一个字体单位
+    let pairs = [
+        (actual.x_min, expected.x_min),
+        (actual.y_min, expected.y_min),
+        (actual.x_max, expected.x_max),
+        (actual.y_max, expected.y_max),
+    ];
+    for (a, b) in pairs {
+        assert!((a - b).abs() <= 1, "{:?} {:?}", actual, expected);
+    }
+    
+    let ttf = service.get_merged_font(&sources, true, true).await.unwrap();
+    assert_eq!(&ttf.data[..4], &[0, 1, 0, 0]);
+    
+    // 所有来源都是CFF字体时没有可以追加字形的基础字体
+    let cff_sources = vec![
+        ("SourceHanSans".to_string(), vec![0x3042]),
+        ("SourceHanSans".to_string(), vec![TEST_CODEPOINT]),
+    ];
+    let result = service.get_merged_font(&cff_sources, false, true).await;
+    assert!(matches!(result, Err(AppError::MergeUnsupported(ids)) if ids == "SourceHanSans"));
+}
+
+#[tokio::test]
+async fn compact_cache_links_identical_subsets() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let font_dir = static_dir.path().join("WenJinMincho");
+    std::fs::create_dir_all(&font_dir).unwrap();
+    std::fs::write(font_dir.join("20013.woff2"), b"same").unwrap();
+    std::fs::write(font_dir.join("20014.woff2"), b"same").unwrap();
+    std::fs::write(font_dir.join("20015.woff2"), b"different").unwrap();
+    // 其他字体的相同内容不参与链接
+    let other_dir = static_dir.path().join("Plangothic");
+    std::fs::create_dir_all(&other_dir).unwrap();
+    std::fs::write(other_dir.join("20013.woff2"), b"same").unwrap();
+    
+    let report = service.compact_cache().await.unwrap();
+    assert_eq!((report.files, report.linked, report.saved_bytes), (4, 1, 4));
+    assert_eq!(std::fs::read(font_dir.join("20014.woff2")).unwrap(), b"same");
+    
+    // 已经链接的文件不会重复处理
+    let report = service.compact_cache().await.unwrap();
+    assert_eq!(report.linked, 0);
+    
+    // 重新生成其中一个文件时不会改写与它链接的文件
+    service.regenerate_subset(Some("WenJinMincho"), &[20014]).await.unwrap();
+    assert_eq!(&std::fs::read(font_dir.join("20014.woff2")).unwrap()[..4], b"wOF2");
+    assert_eq!(std::fs::read(font_dir.join("20013.woff2")).unwrap(), b"same");
+}
+
+#[tokio::test]
+async fn cached_fonts_are_written_in_batches() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let generated = service.get_cached_font("WenJinMincho", &[TEST_CODEPOINT]).await.unwrap();
+    // 落盘前从写入缓冲区读取
+    let cached = service.get_cached_font("WenJinMincho", &[TEST_CODEPOINT]).await.unwrap();
+    assert_eq!(generated.data, cached.data);
+    
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let cache_path = static_dir.path().join("WenJinMincho").join(generate_cache_filename(&[TEST_CODEPOINT]));
+    assert_eq!(std::fs::read(cache_path).unwrap(), generated.data);
+    assert_eq!(service.count_subsets("WenJinMincho").await.unwrap(), 1);
+    assert_eq!(service.count_subsets("Plangothic").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn ttf_subsets_are_cached() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let generated = service.get_cached_ttf("WenJinMincho", &[TEST_CODEPOINT]).await.unwrap();
+    let face = ttf_parser::Face::parse(&generated.data, 0).unwrap();
+    assert!(face.glyph_index(char::from_u32(TEST_CODEPOINT).unwrap()).is_some());
+    
+    let cache_path = static_dir.path().join("WenJinMincho").join(generate_ttf_cache_filename(&[TEST_CODEPOINT]));
+    assert!(cache_path.exists());
+    let cached = service.get_cached_ttf("WenJinMincho", &[TEST_CODEPOINT]).await.unwrap();
+    assert_eq!(cached.data, generated.data);
+}
+
+#[tokio::test]
+async fn rebuild_cache_waits_for_queue_space() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let cache_dir = static_dir.path().join("WenJinMincho");
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    // 超过队列容量的缓存文件，辅助私用区的码点不在任何字体中，重建任务很快失败
+    for codepoint in 0xF0000..0xF0000 + 1100 {
+        std::fs::write(cache_dir.join(generate_cache_filename(&[codepoint])), b"wOF2").unwrap();
+    }
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = Arc::new(FontService::new(config).await.unwrap());
+    BackgroundSubsetter::start(&service);
+    
+    assert_eq!(service.rebuild_cache("WenJinMincho").await.unwrap(), 1100);
+    service.shutdown();
+}
+
+#[tokio::test]
+async fn unhinted_subsets_are_cached_separately() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let generated = service.get_cached_unhinted_font("SourceHanSans", &[TEST_CODEPOINT]).await.unwrap();
+    assert_valid_woff2(&generated.data, TEST_CODEPOINT);
+    
+    let font_dir = static_dir.path().join("SourceHanSans");
+    assert!(font_dir.join(generate_unhinted_cache_filename(&[TEST_CODEPOINT])).exists());
+    assert!(!font_dir.join(generate_cache_filename(&[TEST_CODEPOINT])).exists());
+    
+    // 与带hinting的子集一样沿回退链查找字体自身不包含的字符
+    let hinted = service.get_cached_font("Plangothic", &[0x3042]).await.unwrap();
+    let unhinted = service.get_cached_unhinted_font("Plangothic", &[0x3042]).await.unwrap();
+    assert_valid_woff2(&unhinted.data, 0x3042);
+    assert_eq!(unhinted.glyph_count, hinted.glyph_count);
+    assert!(static_dir.path().join("Plangothic").join(generate_unhinted_cache_filename(&[0x3042])).exists());
+}
+
+#[tokio::test]
+async fn subsets_are_found_by_content_hash() {
+    let static_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        data_dir: data_path("data"),
+        static_dir: static_dir.path().to_path_buf(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config.clone()).await.unwrap();
+    
+    let generated = service.get_cached_unhinted_font("SourceHanSans", &[TEST_CODEPOINT]).await.unwrap();
+    let hash = generate_file_hash(&generated.data);
+    assert_eq!(generated.hash, hash);
+    assert_eq!(generated.glyph_count, FontProcessor::subset_glyph_count(&generated.data));
+    let found = service.get_subset_by_hash("SourceHanSans", &hash).await.unwrap();
+    assert_eq!(found.data, generated.data);
+    assert_eq!(found.glyph_count, generated.glyph_count);
+    assert!(matches!(
+        service.get_subset_by_hash("WenJinMincho", &hash).await,
+        Err(AppError::CacheNotFound(_))
+    ));
+    
+    // 重启后从静态目录重建索引
+    let restarted = FontService::new(config).await.unwrap();
+    let mut found = None;
+    for _ in 0..50 {
+        if let Ok(cached) = restarted.get_subset_by_hash("SourceHanSans", &hash).await {
+            found = Some(cached);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(found.unwrap().data, generated.data);
+    
+    // 文件内容变化后旧哈希失效
+    let cache_path = static_dir.path().join("SourceHanSans").join(generate_unhinted_cache_filename(&[TEST_CODEPOINT]));
+    std::fs::write(&cache_path, b"regenerated").unwrap();
+    assert!(matches!(
+        service.get_subset_by_hash("SourceHanSans", &hash).await,
+        Err(AppError::CacheNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn config_is_generated_for_directories_without_config_json() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let font_dir = data_dir.path().join("fonts").join("my-font");
+    std::fs::create_dir_all(&font_dir).unwrap();
+    std::fs::copy(data_path(TEST_FONT), font_dir.join("mincho.ttf")).unwrap();
+    
+    let detected = FontConfig::detect_from_font_file(&font_dir.join("mincho.ttf")).unwrap();
+    assert_eq!(detected.id, "my-font");
+    assert_eq!(detected.version, "2.001");
+    assert_eq!(detected.files[0].path, "mincho.ttf");
+    assert_eq!(detected.files[0].font_family, "WenJin Mincho Plane 0");
+    
+    let config = AppConfig {
+        data_dir: data_dir.path().to_path_buf(),
+        static_dir: data_dir.path().join("static"),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    let fonts = service.list_fonts().await;
+    assert_eq!(fonts.len(), 1);
+    assert_eq!(fonts[0].id, "my-font");
+    assert!(fonts[0].glyph_count > 0);
+    // 生成的配置与内置字体的配置一样使用4空格缩进
+    let content = std::fs::read_to_string(font_dir.join("config.json")).unwrap();
+    assert!(content.starts_with("{\n    \"id\": \"my-font\""), "{}", content);
+}
+
+#[tokio::test]
+async fn rename_font_moves_directories() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let font_dir = data_dir.path().join("fonts").join("SourceHanSans");
+    std::fs::create_dir_all(&font_dir).unwrap();
+    for entry in std::fs::read_dir(data_path("data/fonts/SourceHanSans")).unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), font_dir.join(entry.file_name())).unwrap();
+    }
+    let static_dir = data_dir.path().join("static");
+    std::fs::create_dir_all(static_dir.join("SourceHanSans")).unwrap();
+    
+    let config = AppConfig {
+        data_dir: data_dir.path().to_path_buf(),
+        static_dir: static_dir.clone(),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config.clone()).await.unwrap();
+    let cached = service.get_cached_unhinted_font("SourceHanSans", &[0x3042]).await.unwrap();
+    
+    service.rename_font("SourceHanSans", "SourceHanSansSC").await.unwrap();
+    // 固定链接随字体ID和缓存目录一起更新
+    let permalink = service.get_subset_by_hash("SourceHanSansSC", &cached.hash).await.unwrap();
+    assert_eq!(permalink.data, cached.data);
+    // 缓存文件扩展属性中的字体ID也已更新，重启后重建的索引使用新ID
+    let entries = service.cache_entries();
+    assert!(!entries.is_empty());
+    assert!(entries.iter().all(|entry| entry.font_id == "SourceHanSansSC"));
+    let restarted = FontService::new(config).await.unwrap();
+    let mut found = None;
+    for _ in 0..50 {
+        if let Ok(permalink) = restarted.get_subset_by_hash("SourceHanSansSC", &cached.hash).await {
+            found = Some(permalink);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(found.unwrap().data, cached.data);
+    assert!(!font_dir.exists());
+    assert!(static_dir.join("SourceHanSansSC").exists());
+    let stored = FontConfig::load_from_dir(&data_dir.path().join("fonts").join("SourceHanSansSC")).unwrap();
+    assert_eq!(stored.id, "SourceHanSansSC");
+    assert!(service.generate_font(Some("SourceHanSansSC"), &[0x3042]).await.is_ok());
+    assert!(matches!(
+        service.generate_font(Some("SourceHanSans"), &[0x3042]).await,
+        Err(AppError::FontNotFound(_))
+    ));
+}
+
+/// 只包含 `config.json` 的字体目录，字体文件缺失时仍能加载
+fn archive_config(id: &str) -> Vec<u8> {
+    let mut config = FontConfig::load_from_dir(&data_path("data/fonts/SourceHanSans")).unwrap();
+    config.id = id.to_string();
+    serde_json::to_vec(&config).unwrap()
+}
+
+#[tokio::test]
+async fn font_archive_rejects_invalid_and_duplicate_ids() {
+    let data_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(data_dir.path().join("fonts")).unwrap();
+    let config = AppConfig {
+        data_dir: data_dir.path().to_path_buf(),
+        static_dir: data_dir.path().join("static"),
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let archive = build_zip(&[
+        ("a/config.json".to_string(), archive_config("shared")),
+        ("b/config.json".to_string(), archive_config("shared")),
+        ("c/config.json".to_string(), archive_config("../escape")),
+    ])
+    .unwrap();
+    let summary = service.install_font_archive(archive).await.unwrap();
+    assert_eq!(summary.fonts, vec!["shared"]);
+    let mut failed: Vec<&str> = summary.failed.iter().map(|failure| failure.name.as_str()).collect();
+    failed.sort();
+    assert_eq!(failed, vec!["b", "c"]);
+    assert!(!data_dir.path().join("fonts/b").exists());
+    assert!(!data_dir.path().join("fonts/c").exists());
+    
+    // 已加载字体的ID也不能被新目录覆盖
+    let archive = build_zip(&[("d/config.json".to_string(), archive_config("shared"))]).unwrap();
+    let summary = service.install_font_archive(archive).await.unwrap();
+    assert_eq!(summary.loaded, 0);
+    assert_eq!(service.list_fonts().await[0].font_family, "Source Han Sans");
+}
+
+#[tokio::test]
+async fn font_archive_extraction_is_size_limited() {
+    let data_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(data_dir.path().join("fonts")).unwrap();
+    let config = AppConfig {
+        data_dir: data_dir.path().to_path_buf(),
+        static_dir: data_dir.path().join("static"),
+        max_archive_extract_bytes: 1024,
+        ..AppConfig::default()
+    };
+    let service = FontService::new(config).await.unwrap();
+    
+    let archive = build_zip(&[
+        ("small/config.json".to_string(), b"{}".to_vec()),
+        ("large/font.ttf".to_string(), vec![0; 4096]),
+    ])
+    .unwrap();
+    assert!(service.install_font_archive(archive).await.is_err());
+    assert!(!data_dir.path().join("fonts/small").exists());
+    assert!(!data_dir.path().join("fonts/large").exists());
+}
@@ -0,0 +1,488 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::from_fn_with_state,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use std::sync::Arc;
+use tower_http::limit::RequestBodyLimitLayer;
+use webfont_zh::{
+    config::AppConfig,
+    error::{ErrorLanguage, ERROR_LANGUAGE},
+    handlers::{self, CoverageQuery, EstimateQuery, FontQuery, ListQuery, MostUsedQuery, RenameRequest, SortOrder},
+    middleware,
+    test_utils::{MockFontService, MOCK_WOFF2},
+    utils::build_zip,
+    AppState,
+};
+
+fn font_query(id: &str, chars: &str) -> Query<FontQuery> {
+    Query(FontQuery {
+        id: id.to_string(),
+        chars: chars.to_string(),
+        format: None,
+        include_fallback: false,
+        warn_tofu: false,
+        hints: true,
+    })
+}
+
+#[tokio::test]
+async fn get_font_returns_woff2() {
+    let mock = Arc::new(MockFontService::new(&["Mock"]));
+    let state: AppState = mock.clone();
+    
+    let response = handlers::get_font(font_query("Mock", "20013,25991"), State(state), HeaderMap::new())
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/font-woff2");
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+    assert_eq!(
+        response.headers()[handlers::CONTENT_DIGEST],
+        "sha-256=:q9WIR/ex5pYRHe/PRorZkDwGGZh4NpK81/hwMBh+/9g=:"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &MOCK_WOFF2[..]);
+    assert_eq!(*mock.recorded.lock().unwrap(), vec![20013, 25991]);
+}
+
+#[tokio::test]
+async fn subset_permalink_uses_content_hash() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    
+    let response = handlers::get_font(font_query("Mock", "20013"), State(state.clone()), HeaderMap::new())
+        .await
+        .unwrap();
+    let hash = response.headers()[handlers::SUBSET_HASH_HEADER].to_str().unwrap().to_string();
+    assert_eq!(hash.len(), 64);
+    
+    let response = handlers::get_subset_by_hash(Path(("Mock".to_string(), hash)), State(state.clone()))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/font-woff2");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &MOCK_WOFF2[..]);
+    
+    let missing = handlers::get_subset_by_hash(Path(("Mock".to_string(), "0".repeat(64))), State(state))
+        .await
+        .unwrap_err();
+    assert_eq!(missing.into_response().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn most_used_fonts_counts_served_subsets() {
+    let mock = Arc::new(MockFontService::new(&["Popular", "Rare"]));
+    let state: AppState = mock.clone();
+    for id in ["Popular", "Rare", "Popular"] {
+        handlers::get_font(font_query(id, "20013"), State(state.clone()), HeaderMap::new())
+            .await
+            .unwrap();
+    }
+    assert!(handlers::get_font(font_query("Missing", "20013"), State(state.clone()), HeaderMap::new())
+        .await
+        .is_err());
+    
+    let response = handlers::most_used_fonts(Query(MostUsedQuery { n: 10 }), State(state.clone())).await;
+    assert_eq!(
+        serde_json::Value::from(response.0),
+        serde_json::json!([{"font_id": "Popular", "requests": 2}, {"font_id": "Rare", "requests": 1}])
+    );
+    let response = handlers::most_used_fonts(Query(MostUsedQuery { n: 1 }), State(state)).await;
+    assert_eq!(response.0.len(), 1);
+}
+
+#[tokio::test]
+async fn least_covered_reports_coverage_percentage() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    
+    let query = CoverageQuery { chars: "20013,57344".to_string() };
+    let response = handlers::least_covered_fonts(Query(query), State(state.clone())).await.unwrap();
+    assert_eq!(
+        serde_json::Value::from(response.0),
+        serde_json::json!([{"font_id": "Mock", "coverage": 50.0}])
+    );
+    
+    let query = CoverageQuery { chars: String::new() };
+    assert!(handlers::least_covered_fonts(Query(query), State(state)).await.is_err());
+}
+
+#[tokio::test]
+async fn get_font_accepts_json_array_codepoints() {
+    let mock = Arc::new(MockFontService::new(&["Mock"]));
+    let state: AppState = mock.clone();
+    
+    let response = handlers::get_font(font_query("Mock", "[20013, 25991]"), State(state), HeaderMap::new())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(*mock.recorded.lock().unwrap(), vec![20013, 25991]);
+}
+
+#[tokio::test]
+async fn get_font_lists_tofu_codepoints() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    
+    let mut query = font_query("Mock", "20013,57344,57345");
+    query.warn_tofu = true;
+    let response = handlers::get_font(query, State(state.clone()), HeaderMap::new())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[handlers::TOFU_HEADER], "57344,57345");
+    
+    let response = handlers::get_font(font_query("Mock", "57344"), State(state), HeaderMap::new())
+        .await
+        .unwrap();
+    assert!(response.headers().get(handlers::TOFU_HEADER).is_none());
+}
+
+#[tokio::test]
+async fn rename_font_to_existing_id_conflicts() {
+    let state: AppState = Arc::new(MockFontService::new(&["Old", "Taken"]));
+    let rename = |new_id: &str| {
+        handlers::rename_font(
+            Path("Old".to_string()),
+            State(state.clone()),
+            Json(RenameRequest { new_id: new_id.to_string() }),
+        )
+    };
+    
+    let error = rename("Taken").await.unwrap_err();
+    assert_eq!(error.into_response().status(), StatusCode::CONFLICT);
+    assert!(rename("New").await.is_ok());
+}
+
+#[tokio::test]
+async fn get_font_unknown_font_is_not_found() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    
+    let error = handlers::get_font(font_query("Missing", "20013"), State(state), HeaderMap::new())
+        .await
+        .unwrap_err();
+    assert_eq!(error.into_response().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn error_message_follows_accept_language() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    let error_body = |language: ErrorLanguage| {
+        let state = state.clone();
+        ERROR_LANGUAGE.scope(language, async move {
+            let error = handlers::get_font(font_query("noto-sans", "20013"), State(state), HeaderMap::new())
+                .await
+                .unwrap_err();
+            let body = to_bytes(error.into_response().into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"].clone()
+        })
+    };
+    
+    assert_eq!(error_body(ErrorLanguage::English).await, "Font not found: noto-sans");
+    assert_eq!(error_body(ErrorLanguage::Chinese).await, "字体未找到: noto-sans");
+    
+    assert_eq!(ErrorLanguage::from_accept_language("en-US,zh;q=0.5"), ErrorLanguage::English);
+    assert_eq!(ErrorLanguage::from_accept_language("fr, zh-TW;q=0.8, en;q=0.5"), ErrorLanguage::Chinese);
+    assert_eq!(ErrorLanguage::from_accept_language("fr"), ErrorLanguage::Chinese);
+}
+
+#[tokio::test]
+async fn list_fonts_wraps_jsonp_callback() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    let list = |callback: &str| {
+        let query = ListQuery { localized: false, callback: Some(callback.to_string()), deduplicate: false, sort: None };
+        handlers::list_fonts(Query(query), State(state.clone()), HeaderMap::new())
+    };
+    
+    let response = list("handleFonts").await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/javascript");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(body.starts_with("handleFonts([{\"id\":\"Mock\""));
+    assert!(body.ends_with("]);"));
+    
+    for callback in ["alert(1)//", "1fn", "a.b", ""] {
+        assert!(list(callback).await.is_err(), "{}", callback);
+    }
+}
+
+#[tokio::test]
+async fn list_fonts_groups_variants_by_family() {
+    let service = MockFontService::new(&["SansBold", "SansRegular", "Serif"])
+        .with_font_family("SansBold", "Sans")
+        .with_font_family("SansRegular", "Sans");
+    let state: AppState = Arc::new(service);
+    let query = ListQuery { localized: false, callback: None, deduplicate: true, sort: None };
+    
+    let response = handlers::list_fonts(Query(query), State(state), HeaderMap::new()).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let fonts: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let fonts = fonts.as_array().unwrap();
+    assert_eq!(fonts.len(), 2);
+    assert_eq!(fonts[0]["font_family"], "Sans");
+    let variant_ids: Vec<&str> = fonts[0]["variants"].as_array().unwrap().iter().map(|v| v["id"].as_str().unwrap()).collect();
+    assert_eq!(variant_ids, ["SansBold", "SansRegular"]);
+    assert!(fonts[1].get("variants").is_none());
+}
+
+#[tokio::test]
+async fn list_fonts_sorts_by_name() {
+    let service = MockFontService::new(&["a", "b", "c"])
+        .with_font_family("a", "Zeta")
+        .with_font_family("b", "Alpha")
+        .with_font_family("c", "Mid");
+    let state: AppState = Arc::new(service);
+    let list = |sort| {
+        let query = ListQuery { localized: false, callback: None, deduplicate: false, sort: Some(sort) };
+        let state = state.clone();
+        async move {
+            let response = handlers::list_fonts(Query(query), State(state), HeaderMap::new()).await.unwrap();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let fonts: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+            fonts.iter().map(|font| font["id"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        }
+    };
+    
+    assert_eq!(list(SortOrder::Alpha).await, ["b", "c", "a"]);
+    assert_eq!(list(SortOrder::Id).await, ["a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn validate_font_config_reports_errors() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    let validate = |body: &'static str| handlers::validate_font_config(State(state.clone()), Bytes::from(body));
+    
+    let Json(result) = validate(r#"{"id": "New", "font_family": "New", "files": [{"name": "R", "path": "New.ttf", "font_family": "New"}]}"#).await;
+    assert_eq!(result, serde_json::json!({ "valid": true }));
+    
+    let Json(result) = validate(r#"{"id": "New", "font_family": "New", "files": []}"#).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["errors"].as_array().unwrap().len(), 1);
+    
+    let Json(result) = validate(r#"{"id": "New"}"#).await;
+    assert_eq!(result["valid"], false);
+}
+
+#[tokio::test]
+async fn get_font_enforces_minimum_codepoints() {
+    let config = AppConfig {
+        min_codepoints_per_request: 2,
+        ..AppConfig::default()
+    };
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]).with_config(config));
+    
+    let error = handlers::get_font(font_query("Mock", "20013"), State(state), HeaderMap::new())
+        .await
+        .unwrap_err();
+    assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn include_fallback_merges_fonts_into_one_response() {
+    let mock = Arc::new(MockFontService::new(&["Main", "Fallback"]));
+    let state: AppState = mock.clone();
+    let mut query = font_query("Main", "20013,131072");
+    query.0.include_fallback = true;
+    
+    let response = handlers::get_font(query, State(state), HeaderMap::new()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/font-woff2");
+    // 合并结果不写入缓存，没有固定链接
+    assert!(!response.headers().contains_key(handlers::SUBSET_HASH_HEADER));
+    assert_eq!(*mock.served.lock().unwrap(), vec!["Main", "Fallback"]);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &MOCK_WOFF2[..]);
+}
+
+#[tokio::test]
+async fn get_font_collection_bundles_all_fonts() {
+    let state: AppState = Arc::new(MockFontService::new(&["A", "B"]));
+    let query = Query(FontQuery {
+        id: "A,B".to_string(),
+        chars: "20013".to_string(),
+        format: Some("collection".to_string()),
+        include_fallback: false,
+        warn_tofu: false,
+        hints: true,
+    });
+    
+    let response = handlers::get_font(query, State(state), HeaderMap::new()).await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/font-collection+json");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let ids: Vec<&str> = json["fonts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|font| font["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["A", "B"]);
+}
+
+#[tokio::test]
+async fn get_font_collection_deduplicates_and_limits_ids() {
+    let mock = Arc::new(MockFontService::new(&["A", "B"]));
+    let state: AppState = mock.clone();
+    let collection_query = |id: String| {
+        Query(FontQuery {
+            id,
+            chars: "20013".to_string(),
+            format: Some("collection".to_string()),
+            include_fallback: false,
+            warn_tofu: false,
+            hints: true,
+        })
+    };
+    
+    // 重复的ID不会在ZIP中产生同名文件
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT, "application/zip".parse().unwrap());
+    let response = handlers::get_font(collection_query("A,A,B".to_string()), State(state.clone()), headers)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+    assert_eq!(*mock.served.lock().unwrap(), vec!["A", "B"]);
+    
+    let ids: Vec<String> = (0..=handlers::MAX_COLLECTION_FONTS).map(|i| format!("F{}", i)).collect();
+    let result = handlers::get_font(collection_query(ids.join(",")), State(state), HeaderMap::new()).await;
+    let response = result.unwrap_err().into_response();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn static_index_lists_no_files_for_empty_cache() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    
+    let response = handlers::static_index_handler(State(state)).await.into_response();
+    assert_eq!(response.status(), StatusCode::OK);
+    
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains("暂无缓存文件"));
+}
+
+#[tokio::test]
+async fn version_reports_build_information() {
+    let response = handlers::version().await;
+    assert_eq!(response.0["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!response.0["git_hash"].as_str().unwrap().is_empty());
+    assert!(response.0["build_timestamp"].as_str().unwrap().ends_with('Z'));
+}
+
+#[tokio::test]
+async fn json_schema_describes_config_types() {
+    let schema = handlers::json_schema(Path("font-config".to_string())).await.unwrap();
+    let schema = serde_json::to_value(&schema.0).unwrap();
+    assert_eq!(schema["title"], "FontConfig");
+    assert!(schema["required"].as_array().unwrap().contains(&"files".into()));
+    // 运行时字段不出现在Schema中
+    assert!(schema["properties"].get("dir").is_none());
+    
+    let error = handlers::json_schema(Path("unknown".to_string())).await.unwrap_err();
+    assert_eq!(error.into_response().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn estimate_returns_estimated_bytes() {
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]));
+    let query = Query(EstimateQuery {
+        id: "Mock".to_string(),
+        chars: "20013,25991".to_string(),
+    });
+    
+    let response = handlers::estimate_subset_size(query, State(state)).await.unwrap();
+    assert_eq!(response.0["estimated_bytes"], 2 * MOCK_WOFF2.len());
+}
+
+#[tokio::test]
+async fn gzip_request_bodies_are_decompressed_before_size_check() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tower::ServiceExt;
+    
+    const LIMIT: usize = 1024;
+    let app = Router::new()
+        .route("/", post(|body: Bytes| async move { body.len().to_string() }))
+        .layer(RequestBodyLimitLayer::new(LIMIT))
+        .layer(from_fn_with_state(LIMIT, middleware::decompress_request));
+    let send = |size: usize| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![b'a'; size]).unwrap();
+        let request = Request::post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::from(encoder.finish().unwrap()))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+    
+    let response = send(LIMIT).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], LIMIT.to_string().as_bytes());
+    
+    // 压缩后远小于限制，解压后超过限制
+    let response = send(LIMIT + 1).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn batch_upload_requires_admin_token() {
+    use tower::ServiceExt;
+    
+    let config = AppConfig {
+        admin_token: Some("secret".to_string()),
+        ..AppConfig::default()
+    };
+    let state: AppState = Arc::new(MockFontService::new(&["Mock"]).with_config(config));
+    let app = handlers::upload_routes(state.clone(), 1024).with_state(state);
+    let mut body = b"--X\r\nContent-Disposition: form-data; name=\"file\"; filename=\"fonts.zip\"\r\n\r\n".to_vec();
+    body.extend(build_zip(&[]).unwrap());
+    body.extend(b"\r\n--X--\r\n");
+    let send = |token: Option<&str>| {
+        let mut request = Request::post("/api/v1/font/batch-upload")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app.clone().oneshot(request.body(Body::from(body.clone())).unwrap())
+    };
+    
+    assert_eq!(send(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(send(Some("wrong")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(send(Some("secret")).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn request_log_appends_json_line_per_request() {
+    use tower::ServiceExt;
+    
+    let dir = tempfile::tempdir().unwrap();
+    let log_path: Arc<std::path::Path> = Arc::from(dir.path().join("access.log").as_path());
+    let app = Router::new()
+        .route("/ok", get(|| async { "ok" }))
+        .layer(from_fn_with_state(log_path, middleware::request_log));
+    
+    for uri in ["/ok?chars=40339", "/missing"] {
+        app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+    }
+    
+    let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(entries.len(), 1);
+    let name = entries[0].to_str().unwrap();
+    assert!(name.starts_with("access.log.") && name.len() == "access.log.YYYY-MM-DD".len());
+    
+    let content = std::fs::read_to_string(dir.path().join(name)).unwrap();
+    let lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["method"], "GET");
+    assert_eq!(lines[0]["path"], "/ok");
+    assert_eq!(lines[0]["query"], "chars=40339");
+    assert_eq!(lines[0]["status"], 200);
+    assert!(lines[0]["latency_ms"].is_u64());
+    assert!(lines[0]["timestamp"].as_str().unwrap().starts_with(&name["access.log.".len()..]));
+    assert_eq!(lines[1]["query"], serde_json::Value::Null);
+    assert_eq!(lines[1]["status"], 404);
+}